@@ -1,16 +1,55 @@
 use pong_lib::TerminalOutput;
-use std::io;
-use std::io::*;
+use pong_lib::error::PongError;
+use pong_lib::menu::{play_local, wait_in_queue, MainMenu};
+use pong_lib::protocol::ServerConnection;
+use pong_lib::replay::{Replay, ReplayPlayer};
+use pong_lib::settings::Settings;
+use pong_lib::theme::Theme;
+use std::path::Path;
 
-use std::net::{IpAddr, TcpListener, TcpStream};
+use std::net::{IpAddr, TcpStream};
 use std::str::FromStr;
-use std::thread;
-use std::process;
 
-fn main() -> io::Result<()>{
+fn main() -> Result<(), PongError> {
     let args: Vec<String> = std::env::args().collect();
     println!("Args: {:?} , Args Length: {}", args, args.len());
 
+    // a broken settings file should not stop anyone playing
+    let (settings, settings_error) = match Settings::load() {
+        Ok(settings) => match Theme::named(&settings.theme) {
+            Ok(_) => (settings, None),
+            Err(error) => {
+                let message = format!("Error: could not load theme {:?}: {}", settings.theme, error);
+                (settings, Some(message))
+            },
+        },
+        Err(error) => (Settings::default(), Some(format!("Error: could not load settings: {}", error))),
+    };
+    if let Some(error) = &settings_error {
+        eprintln!("{}, using the defaults", error);
+    }
+
+    if args.len() == 1 {
+        let mut menu = MainMenu::new(settings);
+        if let Some(error) = settings_error {
+            menu.set_status(error);
+        }
+        return menu.run();
+    }
+
+    if args.len() == 2 && args[1] == "--local" {
+        // both players share this terminal
+        println!("{}", play_local(&settings, false)?);
+        return Ok(());
+    }
+
+    if args.len() == 3 && args[1] == "--replay" {
+        let replay = Replay::load(Path::new(&args[2]))?;
+        let mut player = ReplayPlayer::new(replay)?;
+        player.apply_settings(&settings);
+        return player.run();
+    }
+
     let ip_address = IpAddr::from_str(&args[1])
         .expect("Error parsing Ip Address")
         .to_string();
@@ -19,10 +58,22 @@ fn main() -> io::Result<()>{
 
     println!("server (IP and port): {}", ip_addr_and_port);
 
-    let mut stream = TcpStream::connect(ip_addr_and_port).unwrap();
+    let stream = TcpStream::connect(ip_addr_and_port)?;
+    let mut connection = ServerConnection::join(stream, crossterm::terminal::size()?, settings.player())?;
+    if !wait_in_queue(&settings, &mut connection)? {
+        return Ok(());
+    }
 
-    let mut term = TerminalOutput::new(80, 40);
-    term.run_client(&mut stream)?;
+    // the server picks the arena and runs the simulation, the client only draws it, so
+    // the seed is unused
+    let mut term = TerminalOutput::new(connection.arena(), 0)?;
+    term.apply_settings(&settings);
+    // the server decides who plays, anyone joining after both players is a spectator
+    if connection.side().is_none() {
+        term.run_spectator(&mut connection)?;
+    } else {
+        term.run_client(&mut connection)?;
+    }
 
     Ok(())
 } 
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use crate::serve::ServeState;
+use crate::{GameState, PaddleInput, Side};
+
+/// How well the computer plays.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    // share of the arena width, measured from its own side, inside which the computer
+    // starts following an incoming ball
+    fn reaction_range(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.2,
+            Difficulty::Normal => 0.3,
+            Difficulty::Hard => 1.0,
+        }
+    }
+
+    // the computer sits out one tick in this many, so a steep enough ball outruns it
+    fn rest_every(&self) -> Option<u32> {
+        match self {
+            Difficulty::Easy => Some(2),
+            Difficulty::Normal => Some(3),
+            Difficulty::Hard => None,
+        }
+    }
+}
+
+/// Drives one paddle from the game state. It returns to the middle while the ball heads
+/// away, then follows the ball once it comes close enough; on `Hard` it aims for where
+/// the ball will cross its paddle, bounces included.
+pub struct CpuPlayer {
+    side: Side,
+    difficulty: Difficulty,
+    ticks: u32,
+}
+
+impl CpuPlayer {
+    pub fn new(side: Side, difficulty: Difficulty) -> Self {
+        Self { side, difficulty, ticks: 0 }
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    /// Moves the computer to the other paddle, for a rematch.
+    pub fn switch_sides(&mut self) {
+        self.side = self.side.opposite();
+    }
+
+    pub(crate) fn next_input(&mut self, game: &GameState) -> PaddleInput {
+        self.ticks = self.ticks.wrapping_add(1);
+        if self.difficulty.rest_every().is_some_and(|every| self.ticks.is_multiple_of(every)) {
+            return PaddleInput::Idle;
+        }
+
+        let arena = game.dimensions;
+        let ball = &game.pong_ball;
+        let (paddle, ball_coming, distance, contact_x) = match self.side {
+            Side::PlayerOne => (
+                &game.player,
+                ball.velocity.x < 0.0,
+                ball.position.x - game.player.x as f32,
+                (game.player.x + game.paddle_size.0) as f32,
+            ),
+            Side::PlayerTwo => (
+                &game.opponent,
+                ball.velocity.x > 0.0,
+                game.opponent.x as f32 - ball.position.x,
+                game.opponent.x as f32 - ball.size.0 as f32,
+            ),
+        };
+
+        let in_range = distance <= arena.width as f32 * self.difficulty.reaction_range();
+        let target = if game.serve != ServeState::InPlay || !ball_coming || !in_range {
+            arena.y as f32 + arena.height as f32 / 2.0
+        } else if self.difficulty == Difficulty::Hard {
+            predict_crossing(game, contact_x) + ball.size.1 as f32 / 2.0
+        } else {
+            ball.position.y + ball.size.1 as f32 / 2.0
+        };
+
+        let paddle_centre = paddle.y as f32 + game.paddle_size.1 as f32 / 2.0;
+        // a dead zone stops the paddle jittering around the target
+        if target < paddle_centre - 1.0 {
+            PaddleInput::Up
+        } else if target > paddle_centre + 1.0 {
+            PaddleInput::Down
+        } else {
+            PaddleInput::Idle
+        }
+    }
+}
+
+// height of the ball's top edge when it reaches `x`, folding its path back into the
+// arena every time it would bounce off the top or bottom wall
+fn predict_crossing(game: &GameState, x: f32) -> f32 {
+    let ball = &game.pong_ball;
+    if ball.velocity.x == 0.0 {
+        return ball.position.y;
+    }
+    let ticks = ((x - ball.position.x) / ball.velocity.x).max(0.0);
+    let top = game.dimensions.y as f32 + 1.0;
+    let lowest = (game.dimensions.y + game.dimensions.height) as f32 - 1.0 - ball.size.1 as f32;
+    let span = lowest - top;
+    if span <= 0.0 {
+        return ball.position.y;
+    }
+
+    let travelled = (ball.position.y - top + ball.velocity.y * ticks).rem_euclid(2.0 * span);
+    top + if travelled > span { 2.0 * span - travelled } else { travelled }
+}
@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use crate::Side;
+
+/// Longest chat message, in characters. The server cuts longer ones short.
+pub const MAX_CHAT_LEN: usize = 80;
+
+/// Messages anyone may send within `CHAT_WINDOW`, the server drops any more.
+const CHAT_BURST: usize = 3;
+const CHAT_WINDOW: Duration = Duration::from_secs(5);
+
+/// Lines the chat panel keeps.
+const CHAT_HISTORY: usize = 50;
+
+/// A chat message as the server passes it on to everyone.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ChatLine {
+    /// Tick of the match it was sent on.
+    pub tick: u32,
+    /// Name of whoever wrote it, `None` for a notice from the server.
+    pub sender: Option<String>,
+    /// The sender's paddle, `None` for spectators and notices.
+    pub side: Option<Side>,
+    pub text: String,
+}
+
+impl ChatLine {
+    pub fn notice(tick: u32, text: impl Into<String>) -> Self {
+        Self { tick, sender: None, side: None, text: text.into() }
+    }
+}
+
+/// `text` as the server passes it on, without control characters, trimmed and cut to
+/// `MAX_CHAT_LEN`. `None` if nothing is left.
+pub(crate) fn clean(text: &str) -> Option<String> {
+    let text: String = text.chars().filter(|character| !character.is_control()).collect();
+    let text: String = text.trim().chars().take(MAX_CHAT_LEN).collect();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Stops one sender flooding the chat.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ChatLimiter {
+    /// When their recent messages were sent, oldest first.
+    sent: VecDeque<Instant>,
+}
+
+impl ChatLimiter {
+    /// What a sender is told when `allow` turns them down.
+    pub fn refusal(tick: u32) -> ChatLine {
+        ChatLine::notice(tick, format!("Slow down, at most {} messages every {} seconds", CHAT_BURST, CHAT_WINDOW.as_secs()))
+    }
+
+    /// Whether a message sent `now` may go through, counting it if so.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        while self.sent.front().is_some_and(|sent| now.duration_since(*sent) >= CHAT_WINDOW) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= CHAT_BURST {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+/// The client's side of the chat: the lines so far and the message being typed.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ChatPanel {
+    lines: VecDeque<ChatLine>,
+    /// Text typed so far, `None` when not typing.
+    draft: Option<String>,
+    open: bool,
+    /// Finished messages waiting to be sent.
+    outbox: Vec<String>,
+    /// Shows the chat without a way to add to it, for replays.
+    read_only: bool,
+}
+
+impl ChatPanel {
+    pub fn read_only() -> Self {
+        Self { read_only: true, ..Self::default() }
+    }
+
+    /// Adds a line, opening the panel so it gets seen.
+    pub fn receive(&mut self, line: ChatLine) {
+        self.lines.push_back(line);
+        if self.lines.len() > CHAT_HISTORY {
+            self.lines.pop_front();
+        }
+        self.open = true;
+    }
+
+    pub fn start_typing(&mut self) {
+        if self.read_only {
+            return;
+        }
+        self.open = true;
+        self.draft.get_or_insert_with(String::new);
+    }
+
+    pub fn is_typing(&self) -> bool {
+        self.draft.is_some()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Lines received, oldest first.
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &ChatLine> {
+        self.lines.iter()
+    }
+
+    pub fn draft(&self) -> Option<&str> {
+        self.draft.as_deref()
+    }
+
+    /// Types `key` into the message being written. Enter finishes it and Esc throws it
+    /// away and closes the panel. Returns false when not typing, leaving the key to the
+    /// game.
+    pub fn type_key(&mut self, key: &KeyEvent) -> bool {
+        let Some(draft) = &mut self.draft else {
+            return false;
+        };
+        match key.code {
+            // Ctrl-C is left to the game, so it still quits
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Enter => {
+                self.outbox.push(std::mem::take(draft));
+                self.draft = None;
+            },
+            KeyCode::Esc => {
+                self.draft = None;
+                self.open = false;
+            },
+            KeyCode::Backspace => {
+                draft.pop();
+            },
+            KeyCode::Char(character)
+                if !key.modifiers.contains(KeyModifiers::CONTROL) && draft.chars().count() < MAX_CHAT_LEN =>
+            {
+                draft.push(character);
+            },
+            _ => {},
+        }
+        true
+    }
+
+    /// Messages finished since the last call, to send to the server.
+    pub fn take_outbox(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.outbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_burst_is_allowed_once_per_window() {
+        let start = Instant::now();
+        let mut limiter = ChatLimiter::default();
+        for second in 0..CHAT_BURST as u64 {
+            assert!(limiter.allow(start + Duration::from_secs(second)));
+        }
+        assert!(!limiter.allow(start + Duration::from_secs(4)));
+
+        // the first message leaves the window after five seconds, making room for one more
+        assert!(limiter.allow(start + CHAT_WINDOW));
+        assert!(!limiter.allow(start + CHAT_WINDOW));
+    }
+
+    #[test]
+    fn messages_are_cleaned_and_cut_short() {
+        assert_eq!(clean("  hi\tthere\u{7}\n "), Some("hithere".to_string()));
+        assert_eq!(clean(" \r\n"), None);
+        assert_eq!(clean(""), None);
+
+        let long = "é".repeat(MAX_CHAT_LEN + 20);
+        assert_eq!(clean(&long).unwrap().chars().count(), MAX_CHAT_LEN);
+    }
+}
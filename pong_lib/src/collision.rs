@@ -0,0 +1,173 @@
+use std::ops::{Add, Mul, Sub};
+use ratatui::layout::Rect;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn length(self) -> f32 {
+        self.x.hypot(self.y)
+    }
+
+    /// Mirrors the vector along `normal`, flipping whichever axes the normal points down.
+    pub fn reflect(self, normal: Vec2) -> Self {
+        Self {
+            x: if normal.x != 0.0 { -self.x } else { self.x },
+            y: if normal.y != 0.0 { -self.y } else { self.y },
+        }
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, scale: f32) -> Vec2 {
+        Vec2::new(self.x * scale, self.y * scale)
+    }
+}
+
+/// Axis aligned box, open on every side so that merely touching a face is not an overlap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Aabb {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_rect(rect: Rect) -> Self {
+        Self::new(
+            Vec2::new(rect.x as f32, rect.y as f32),
+            Vec2::new((rect.x + rect.width) as f32, (rect.y + rect.height) as f32),
+        )
+    }
+
+    /// Grows the box up and to the left by the size of a moving body, so that the
+    /// body's top-left corner can be swept as a single point against it.
+    pub fn expand(&self, size: Vec2) -> Self {
+        Self::new(self.min - size, self.max)
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x > self.min.x && point.x < self.max.x &&
+            point.y > self.min.y && point.y < self.max.y
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// Fraction of the swept segment travelled before contact, in `[0, 1]`.
+    pub time: f32,
+    /// Outward normal of the face that was hit.
+    pub normal: Vec2,
+}
+
+/// Sweeps the segment `start..start + delta` against `aabb` and returns the first
+/// face it enters. Segments that start inside the box, only graze an edge or move
+/// away from the face they touch do not count as hits.
+pub fn sweep_point(start: Vec2, delta: Vec2, aabb: &Aabb) -> Option<Hit> {
+    let mut entry = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+
+    let axes = [
+        (start.x, delta.x, aabb.min.x, aabb.max.x, Vec2::new(1.0, 0.0)),
+        (start.y, delta.y, aabb.min.y, aabb.max.y, Vec2::new(0.0, 1.0)),
+    ];
+
+    for (start, delta, min, max, axis) in axes {
+        if delta == 0.0 {
+            if start <= min || start >= max {
+                return None;
+            }
+            continue;
+        }
+
+        let (near, far, face) = if delta > 0.0 {
+            ((min - start) / delta, (max - start) / delta, axis * -1.0)
+        } else {
+            ((max - start) / delta, (min - start) / delta, axis)
+        };
+
+        if near > entry {
+            entry = near;
+            normal = face;
+        }
+        exit = exit.min(far);
+    }
+
+    if entry >= exit || !(0.0..=1.0).contains(&entry) {
+        return None;
+    }
+
+    Some(Hit { time: entry, normal })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vec2::new(4.0, 4.0), Vec2::new(6.0, 6.0))
+    }
+
+    #[test]
+    fn entering_a_face_hits_it() {
+        let hit = sweep_point(Vec2::new(0.0, 5.0), Vec2::new(10.0, 0.0), &unit_box()).unwrap();
+        assert_eq!(hit, Hit { time: 0.4, normal: Vec2::new(-1.0, 0.0) });
+
+        let hit = sweep_point(Vec2::new(5.0, 10.0), Vec2::new(0.0, -8.0), &unit_box()).unwrap();
+        assert_eq!(hit, Hit { time: 0.5, normal: Vec2::new(0.0, 1.0) });
+    }
+
+    #[test]
+    fn stopping_short_misses() {
+        assert_eq!(sweep_point(Vec2::new(0.0, 5.0), Vec2::new(3.0, 0.0), &unit_box()), None);
+    }
+
+    #[test]
+    fn grazing_an_edge_misses() {
+        // along the top face, and through the corner only
+        assert_eq!(sweep_point(Vec2::new(0.0, 4.0), Vec2::new(10.0, 0.0), &unit_box()), None);
+        assert_eq!(sweep_point(Vec2::new(2.0, 6.0), Vec2::new(4.0, -4.0), &unit_box()), None);
+    }
+
+    #[test]
+    fn starting_inside_misses() {
+        assert_eq!(sweep_point(Vec2::new(5.0, 5.0), Vec2::new(3.0, 0.0), &unit_box()), None);
+    }
+
+    #[test]
+    fn moving_away_misses() {
+        assert_eq!(sweep_point(Vec2::new(6.0, 5.0), Vec2::new(2.0, 0.0), &unit_box()), None);
+        assert_eq!(sweep_point(Vec2::new(8.0, 5.0), Vec2::new(2.0, 1.0), &unit_box()), None);
+    }
+}
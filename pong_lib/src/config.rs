@@ -0,0 +1,153 @@
+use std::{error::Error, fmt};
+use std::path::PathBuf;
+use ratatui::layout::Rect;
+use serde::{Deserialize, Serialize};
+use crate::settings::MAX_NAME_LEN;
+use crate::view::ArenaView;
+
+/// Reasons an arena/paddle configuration cannot be simulated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The paddle has no width or no height.
+    EmptyPaddle { paddle_size: (u16, u16) },
+    /// The arena's far edges do not fit in a `u16` coordinate.
+    ArenaOutOfRange { dimensions: Rect },
+    /// The arena cannot fit both paddles, the borders and room for the ball.
+    ArenaTooSmall { width: u16, height: u16, min_width: u16, min_height: u16 },
+    /// The match rules can never produce a result.
+    InvalidRules { reason: &'static str },
+    /// A player's terminal cannot display the arena.
+    TerminalTooSmall { needed: (u16, u16), available: (u16, u16) },
+    /// Paddles would never move.
+    StillPaddle,
+    /// A key binding names a key that does not exist.
+    UnknownKey { name: String },
+    /// A player name that is empty, too long or has control characters in it.
+    InvalidName { name: String },
+    /// A settings or theme file could not be understood.
+    InvalidFile { path: PathBuf, reason: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::EmptyPaddle { paddle_size } => write!(
+                f, "paddle size {}x{} must be at least 1x1", paddle_size.0, paddle_size.1
+            ),
+            ConfigError::ArenaOutOfRange { dimensions } => write!(
+                f, "arena {:?} extends past the largest drawable coordinate", dimensions
+            ),
+            ConfigError::ArenaTooSmall { width, height, min_width, min_height } => write!(
+                f, "arena {}x{} is too small, it must be at least {}x{}",
+                width, height, min_width, min_height
+            ),
+            ConfigError::InvalidRules { reason } => write!(f, "invalid match rules: {}", reason),
+            ConfigError::TerminalTooSmall { needed, available } => write!(
+                f, "the arena needs a terminal of at least {}x{}, this one is {}x{}",
+                needed.0, needed.1, available.0, available.1
+            ),
+            ConfigError::StillPaddle => write!(f, "paddle speed must be at least 1 row per tick"),
+            ConfigError::UnknownKey { name } => write!(f, "unknown key {:?}", name),
+            ConfigError::InvalidName { name } => write!(
+                f, "player names must be 1 to {} characters with no control characters, not {:?}",
+                MAX_NAME_LEN, name
+            ),
+            ConfigError::InvalidFile { path, reason } => write!(f, "{}: {}", path.display(), reason),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Size of the arena and paddles. The host of a match picks it and every player and
+/// spectator draws the same one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ArenaConfig {
+    pub width: u16,
+    pub height: u16,
+    pub paddle_width: u16,
+    pub paddle_height: u16,
+    /// Rows a paddle moves each tick its key is held. Arenas saved before paddles had a
+    /// speed moved one row.
+    #[serde(default = "default_paddle_speed")]
+    pub paddle_speed: u16,
+}
+
+fn default_paddle_speed() -> u16 {
+    1
+}
+
+impl Default for ArenaConfig {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            height: 40,
+            paddle_width: 2,
+            paddle_height: 4,
+            paddle_speed: default_paddle_speed(),
+        }
+    }
+}
+
+impl ArenaConfig {
+    pub fn dimensions(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+
+    pub fn paddle_size(&self) -> (u16, u16) {
+        (self.paddle_width, self.paddle_height)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.paddle_speed == 0 {
+            return Err(ConfigError::StillPaddle);
+        }
+        validate_arena(self.dimensions(), self.paddle_size())
+    }
+
+    /// Smallest terminal, in columns and rows, the arena can be drawn in.
+    pub fn min_terminal_size(&self) -> (u16, u16) {
+        ArenaView::min_size(self.dimensions())
+    }
+
+    /// Checks that a terminal of `available` columns and rows can draw the arena.
+    pub fn check_fits(&self, available: (u16, u16)) -> Result<(), ConfigError> {
+        let needed = self.min_terminal_size();
+        if available.0 < needed.0 || available.1 < needed.1 {
+            return Err(ConfigError::TerminalTooSmall { needed, available });
+        }
+        Ok(())
+    }
+}
+
+/// Smallest arena that fits a paddle of `paddle_size` on each side. Horizontally that
+/// is a border and a paddle per side plus three free columns for the ball; vertically
+/// the paddle must fit between the borders with a row to spare.
+pub fn min_arena_size(paddle_size: (u16, u16)) -> (u16, u16) {
+    let (paddle_width, paddle_height) = paddle_size;
+    (
+        paddle_width.saturating_mul(2).saturating_add(5),
+        paddle_height.saturating_add(3),
+    )
+}
+
+/// Checks that a `GameState` built from `dimensions` and `paddle_size` keeps every
+/// position inside the arena without overflowing.
+pub fn validate_arena(dimensions: Rect, paddle_size: (u16, u16)) -> Result<(), ConfigError> {
+    let Rect { x, y, width, height } = dimensions;
+
+    if paddle_size.0 == 0 || paddle_size.1 == 0 {
+        return Err(ConfigError::EmptyPaddle { paddle_size });
+    }
+
+    if x.checked_add(width).is_none() || y.checked_add(height).is_none() {
+        return Err(ConfigError::ArenaOutOfRange { dimensions });
+    }
+
+    let (min_width, min_height) = min_arena_size(paddle_size);
+    if width < min_width || height < min_height {
+        return Err(ConfigError::ArenaTooSmall { width, height, min_width, min_height });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use ratatui::style::Color;
+use crate::collision::Vec2;
+use crate::events::{GameEvent, Wall};
+use crate::{Side, TICKS_PER_SECOND};
+
+/// Earlier ball positions drawn behind it.
+const TRAIL_LENGTH: usize = 6;
+
+/// Ticks a paddle or wall stays lit after the ball hits it, long enough to show at the
+/// lowest frame rate.
+const FLASH_TICKS: u16 = 4;
+
+/// Ticks the score animation plays for, it finishes before the next serve.
+const SCORE_TICKS: u16 = 2 * TICKS_PER_SECOND;
+
+/// A stretch of wall the ball bounced off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct WallFlash {
+    pub wall: Wall,
+    /// Logical column the ball hit at.
+    pub x: u16,
+    ticks_left: u16,
+}
+
+/// The animation after a point, bigger when it also won a game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ScoreAnimation {
+    pub scorer: Side,
+    pub game_won: bool,
+    /// Ticks since the point.
+    pub age: u16,
+}
+
+/// Visual effects that are not part of the game, started by the events each tick
+/// produces and counted down tick by tick, so they play at the same pace at any frame
+/// rate.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Effects {
+    /// Where the ball was over the last few ticks, oldest first and ending where it is now.
+    trail: VecDeque<Vec2>,
+    paddle_flashes: [u16; 2],
+    wall_flashes: Vec<WallFlash>,
+    score: Option<ScoreAnimation>,
+}
+
+impl Effects {
+    /// Moves every effect on by one tick, then starts the ones `events` call for. `ball`
+    /// is where the ball was at the end of the tick.
+    pub fn update(&mut self, ball: Vec2, events: &[GameEvent]) {
+        for ticks_left in &mut self.paddle_flashes {
+            *ticks_left = ticks_left.saturating_sub(1);
+        }
+        self.wall_flashes.retain_mut(|flash| {
+            flash.ticks_left -= 1;
+            flash.ticks_left > 0
+        });
+        if let Some(score) = &mut self.score {
+            score.age += 1;
+        }
+        self.score = self.score.filter(|score| score.age < SCORE_TICKS);
+
+        self.trail.push_back(ball);
+        if self.trail.len() > TRAIL_LENGTH + 1 {
+            self.trail.pop_front();
+        }
+
+        for event in events {
+            match *event {
+                GameEvent::PaddleHit { side } => self.paddle_flashes[side as usize] = FLASH_TICKS,
+                GameEvent::WallBounce { wall, x } => {
+                    self.wall_flashes.push(WallFlash { wall, x, ticks_left: FLASH_TICKS });
+                },
+                // the ball jumps back to the middle, a trail would streak across the arena
+                GameEvent::PointScored { scorer } => {
+                    self.trail.clear();
+                    self.score = Some(ScoreAnimation { scorer, game_won: false, age: 0 });
+                },
+                GameEvent::GameWon { winner, .. } => {
+                    self.score = Some(ScoreAnimation { scorer: winner, game_won: true, age: 0 });
+                },
+                // the result takes over the screen
+                GameEvent::MatchOver(_) => *self = Self::default(),
+            }
+        }
+    }
+
+    /// Earlier positions of the ball, oldest first, with how strongly each is drawn
+    /// from just above 0 to just below 1. Places the ball still is are left out.
+    pub fn trail(&self) -> impl Iterator<Item = (Vec2, f32)> + '_ {
+        let ball = self.trail.back().copied();
+        let older = self.trail.len().saturating_sub(1);
+        self.trail
+            .iter()
+            .take(older)
+            .enumerate()
+            .filter(move |(_, position)| Some(**position) != ball)
+            .map(move |(age, position)| (*position, (age + 1 + TRAIL_LENGTH - older) as f32 / (TRAIL_LENGTH + 1) as f32))
+    }
+
+    pub fn paddle_lit(&self, side: Side) -> bool {
+        self.paddle_flashes[side as usize] > 0
+    }
+
+    pub fn wall_flashes(&self) -> &[WallFlash] {
+        &self.wall_flashes
+    }
+
+    pub fn score(&self) -> Option<ScoreAnimation> {
+        self.score
+    }
+}
+
+/// `color` blended into `background` by `strength`, from 0 for all background to 1 for
+/// all colour. Colours that are not RGB cannot be blended, so grey stands in for them.
+pub(crate) fn fade(color: Color, background: Color, strength: f32) -> Color {
+    let strength = strength.clamp(0.0, 1.0);
+    let mix = |from: u8, to: u8| (to as f32 + (from as f32 - to as f32) * strength).round() as u8;
+    match (color, background) {
+        (Color::Rgb(r, g, b), Color::Rgb(back_r, back_g, back_b)) => {
+            Color::Rgb(mix(r, back_r), mix(g, back_g), mix(b, back_b))
+        },
+        (Color::Rgb(r, g, b), _) => Color::Rgb(mix(r, 0), mix(g, 0), mix(b, 0)),
+        _ if strength >= 0.5 => Color::Gray,
+        _ => Color::DarkGray,
+    }
+}
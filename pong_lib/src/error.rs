@@ -0,0 +1,71 @@
+use std::{error::Error, fmt, io};
+use crate::config::ConfigError;
+
+/// Everything that can go wrong running, hosting or joining a game.
+#[derive(Debug)]
+pub enum PongError {
+    /// Reading or writing the terminal, a socket or a file failed.
+    Io(io::Error),
+    /// A message or file was malformed, or not what the protocol expects at that point.
+    Protocol(String),
+    /// The other end, or a replay file, was made by an incompatible build.
+    VersionMismatch { what: &'static str, expected: u32, found: u32 },
+    /// The arena, paddles or rules cannot be played.
+    Config(ConfigError),
+    /// The other end closed the connection.
+    Disconnected,
+    /// A ranked server turned the player away, someone with the same name is already
+    /// queued or playing there.
+    NameInUse { name: String },
+}
+
+impl fmt::Display for PongError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PongError::Io(error) => write!(f, "{}", error),
+            PongError::Protocol(reason) => write!(f, "protocol error: {}", reason),
+            PongError::VersionMismatch { what, expected, found } => write!(
+                f, "{} version {} is not supported, expected {}", what, found, expected
+            ),
+            PongError::Config(error) => write!(f, "{}", error),
+            PongError::Disconnected => write!(f, "the connection was closed"),
+            PongError::NameInUse { name } => write!(f, "a player called {:?} is already on the server", name),
+        }
+    }
+}
+
+impl Error for PongError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PongError::Io(error) => Some(error),
+            PongError::Config(error) => Some(error),
+            PongError::Protocol(_)
+            | PongError::VersionMismatch { .. }
+            | PongError::Disconnected
+            | PongError::NameInUse { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for PongError {
+    fn from(error: io::Error) -> Self {
+        PongError::Io(error)
+    }
+}
+
+impl From<ConfigError> for PongError {
+    fn from(error: ConfigError) -> Self {
+        PongError::Config(error)
+    }
+}
+
+impl From<serde_json::Error> for PongError {
+    fn from(error: serde_json::Error) -> Self {
+        // serde_json reports failures of the underlying reader or writer as its own errors
+        if error.is_io() {
+            PongError::Io(error.into())
+        } else {
+            PongError::Protocol(error.to_string())
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+use crate::rules::MatchResult;
+use crate::Side;
+
+/// Things that happened during a simulation tick, for frontends to react to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum GameEvent {
+    /// The ball came off a paddle, or a paddle moved into it.
+    PaddleHit { side: Side },
+    /// The ball bounced off a wall, `x` is the column its middle was in.
+    WallBounce { wall: Wall, x: u16 },
+    PointScored { scorer: Side },
+    GameWon { winner: Side, player_one_games: u16, player_two_games: u16 },
+    MatchOver(MatchResult),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Wall {
+    Top,
+    Bottom,
+}
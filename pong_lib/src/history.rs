@@ -0,0 +1,234 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::error::PongError;
+use crate::rating::{rating_changes, DEFAULT_RATING};
+use crate::rules::MatchResult;
+use crate::Side;
+
+/// Where a server keeps its match history unless told otherwise.
+pub const DEFAULT_HISTORY_PATH: &str = "history/matches.jsonl";
+
+/// Port a server serves its history over HTTP on unless told otherwise.
+pub const DEFAULT_API_PORT: u16 = 3738;
+
+/// How long `fetch_player_stats` gives the server to connect and answer.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A finished match as the history keeps it.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MatchRecord {
+    /// When it finished, in seconds since the Unix epoch.
+    pub finished_at: u64,
+    /// Who played, player one first.
+    pub players: [String; 2],
+    pub result: MatchResult,
+    /// The replay it was saved to, if it was recorded.
+    pub replay: Option<PathBuf>,
+}
+
+impl MatchRecord {
+    /// A record of a match finishing now.
+    pub fn new(players: [String; 2], result: MatchResult, replay: Option<PathBuf>) -> Self {
+        let finished_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        Self { finished_at, players, result, replay }
+    }
+
+    /// The end `name` played, if they played in this match.
+    pub fn side_of(&self, name: &str) -> Option<Side> {
+        [Side::PlayerOne, Side::PlayerTwo]
+            .into_iter()
+            .find(|side| self.players[*side as usize] == name)
+    }
+}
+
+/// One player's totals over every match in the history.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PlayerStats {
+    pub name: String,
+    /// Elo rating after their last match, `DEFAULT_RATING` before their first.
+    pub rating: i32,
+    /// How much their last match moved `rating`.
+    pub rating_change: i32,
+    pub matches: u32,
+    pub wins: u32,
+    /// Matches lost, forfeits included. Draws count as neither a win nor a loss.
+    pub losses: u32,
+    pub points_for: u32,
+    pub points_against: u32,
+    /// Most paddle hits in a rally in any of their matches.
+    pub longest_rally: u32,
+}
+
+impl PlayerStats {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), rating: DEFAULT_RATING, ..Self::default() }
+    }
+
+    /// Adds a match they played on `side`, which moved their rating by `rating_change`.
+    pub fn add(&mut self, record: &MatchRecord, side: Side, rating_change: i32) {
+        let result = &record.result;
+        self.matches += 1;
+        self.rating += rating_change;
+        self.rating_change = rating_change;
+        match result.winner {
+            Some(winner) if winner == side => self.wins += 1,
+            Some(_) => self.losses += 1,
+            None => {},
+        }
+        self.points_for += result.stats.points[side as usize];
+        self.points_against += result.stats.points[side.opposite() as usize];
+        self.longest_rally = self.longest_rally.max(result.stats.longest_rally);
+    }
+}
+
+/// Every match a server has finished, one JSON record per line of a file that only
+/// ever grows.
+#[derive(Clone, Debug)]
+pub struct MatchHistory {
+    path: PathBuf,
+}
+
+impl MatchHistory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, record: &MatchRecord) -> Result<(), PongError> {
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        // one write per record, so a reader never sees half of one
+        OpenOptions::new().create(true).append(true).open(&self.path)?.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Every match so far, oldest first. A history that does not exist yet is empty.
+    pub fn load(&self) -> Result<Vec<MatchRecord>, PongError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+        let mut records = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(&line).map_err(|error| {
+                PongError::Protocol(format!("{} line {}: {}", self.path.display(), number + 1, error))
+            })?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    /// Totals for everyone who has played, highest rated first. Ratings are worked out
+    /// afresh from the whole history, match by match in the order they finished. Matches
+    /// between two players of the same name cannot be told apart and are left out.
+    pub fn player_stats(&self) -> Result<Vec<PlayerStats>, PongError> {
+        let mut players: Vec<PlayerStats> = Vec::new();
+        for record in self.load()? {
+            // both sides would fold into one player, a win and a loss at once
+            if record.players[0] == record.players[1] {
+                continue;
+            }
+            let indices = record.players.clone().map(|name| {
+                match players.iter().position(|player| player.name == name) {
+                    Some(index) => index,
+                    None => {
+                        players.push(PlayerStats::new(name));
+                        players.len() - 1
+                    },
+                }
+            });
+            let changes = rating_changes(indices.map(|index| players[index].rating), record.result.winner);
+            for side in [Side::PlayerOne, Side::PlayerTwo] {
+                players[indices[side as usize]].add(&record, side, changes[side as usize]);
+            }
+        }
+        players.sort_by(|a, b| b.rating.cmp(&a.rating).then_with(|| a.name.cmp(&b.name)));
+        Ok(players)
+    }
+
+    /// Totals for `name`, a fresh rating and no matches if they have not played yet.
+    pub fn stats_for(&self, name: &str) -> Result<PlayerStats, PongError> {
+        let players = self.player_stats()?;
+        Ok(players.into_iter().find(|player| player.name == name).unwrap_or_else(|| PlayerStats::new(name)))
+    }
+}
+
+/// Everyone's totals, highest rated first, from the HTTP API at `address`.
+pub fn fetch_player_stats(address: SocketAddr) -> Result<Vec<PlayerStats>, PongError> {
+    let mut stream = TcpStream::connect_timeout(&address, FETCH_TIMEOUT)?;
+    stream.set_read_timeout(Some(FETCH_TIMEOUT))?;
+    // HTTP/1.0 so the server closes the connection once it has answered
+    write!(stream, "GET /players HTTP/1.0\r\nHost: {}\r\n\r\n", address)?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| PongError::Protocol(String::from("incomplete HTTP response")))?;
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(PongError::Protocol(format!("the server answered {}", status)));
+    }
+    Ok(serde_json::from_str(body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{MatchEnd, MatchStats};
+
+    // a history in a file of its own under the temp directory, empty to start with
+    fn empty_history(name: &str) -> MatchHistory {
+        let path = std::env::temp_dir().join(format!("rustypong-{}-{}.jsonl", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        MatchHistory::new(path)
+    }
+
+    fn record(players: [&str; 2], winner: Side) -> MatchRecord {
+        let mut stats = MatchStats::default();
+        stats.points[winner as usize] = 1;
+        let result = MatchResult {
+            winner: Some(winner),
+            reason: MatchEnd::GamesWon,
+            player_one_games: (winner == Side::PlayerOne) as u16,
+            player_two_games: (winner == Side::PlayerTwo) as u16,
+            player_one_score: stats.points[0] as u16,
+            player_two_score: stats.points[1] as u16,
+            elapsed_ticks: 100,
+            stats,
+        };
+        MatchRecord::new(players.map(String::from), result, None)
+    }
+
+    #[test]
+    fn same_name_matches_are_left_out() {
+        let history = empty_history("same-name");
+        history.append(&record(["Player", "Player"], Side::PlayerOne)).unwrap();
+        history.append(&record(["Player", "Ann"], Side::PlayerOne)).unwrap();
+
+        let players = history.player_stats().unwrap();
+        let player = players.iter().find(|player| player.name == "Player").unwrap();
+        assert_eq!((player.matches, player.wins, player.losses), (1, 1, 0));
+        assert_eq!(player.rating, DEFAULT_RATING + 16);
+        assert_eq!(history.stats_for("Ann").unwrap().rating, DEFAULT_RATING - 16);
+        fs::remove_file(history.path()).unwrap();
+    }
+}
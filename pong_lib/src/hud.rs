@@ -0,0 +1,42 @@
+use crate::collision::Vec2;
+use crate::events::GameEvent;
+use crate::TICKS_PER_SECOND;
+
+/// Figures for the status bar that snapshots do not carry, worked out from where the
+/// ball is after each tick and the events the tick produced.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RallyStats {
+    /// Paddle hits since the last serve.
+    pub rally: u16,
+    /// How fast the ball is moving, in arena cells per second.
+    pub speed: f32,
+    last_ball: Option<Vec2>,
+}
+
+impl RallyStats {
+    pub fn update(&mut self, ball: Vec2, events: &[GameEvent]) {
+        // a bounce shortens the distance covered in its tick, the speed from before it stands
+        let bounced = events
+            .iter()
+            .any(|event| matches!(event, GameEvent::PaddleHit { .. } | GameEvent::WallBounce { .. }));
+        if let (Some(last), false) = (self.last_ball, bounced) {
+            self.speed = (ball - last).length() * TICKS_PER_SECOND as f32;
+        }
+        self.last_ball = Some(ball);
+
+        for event in events {
+            match event {
+                GameEvent::PaddleHit { .. } => self.rally = self.rally.saturating_add(1),
+                // the ball jumps back to the middle to wait for the next serve
+                GameEvent::PointScored { .. } | GameEvent::MatchOver(_) => *self = Self::default(),
+                GameEvent::WallBounce { .. } | GameEvent::GameWon { .. } => {},
+            }
+        }
+    }
+}
+
+/// `ticks` as minutes and seconds, e.g. `2:05`.
+pub(crate) fn clock(ticks: u32) -> String {
+    let secs = ticks / TICKS_PER_SECOND as u32;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
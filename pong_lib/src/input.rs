@@ -0,0 +1,402 @@
+use std::fmt;
+use std::str::FromStr;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use crate::config::ConfigError;
+use crate::{PaddleInput, Side};
+
+/// What a key press asks the game to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    /// Pauses the match, or votes to resume it.
+    Pause,
+    Quit,
+    /// Starts typing a chat message.
+    Chat,
+    /// Asks to play again once the match is over.
+    Rematch,
+}
+
+/// A key together with the modifiers that must be held with it. Saved as text such as
+/// `w`, `Up`, `Space` or `Ctrl+c`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+// keys with a name of their own, a single character stands for itself
+const KEY_NAMES: [(&str, KeyCode); 14] = [
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Space", KeyCode::Char(' ')),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+];
+
+impl Key {
+    pub const fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    const fn plain(code: KeyCode) -> Self {
+        Self::new(code, KeyModifiers::NONE)
+    }
+
+    const fn char(character: char) -> Self {
+        Self::plain(KeyCode::Char(character))
+    }
+
+    /// The key `event` reports. Shift is left out, it is already part of the character.
+    pub fn from_event(event: &KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers - KeyModifiers::SHIFT)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        match (KEY_NAMES.iter().find(|(_, code)| *code == self.code), self.code) {
+            (Some((name, _)), _) => write!(f, "{}", name),
+            (None, KeyCode::Char(character)) => write!(f, "{}", character),
+            (None, KeyCode::F(number)) => write!(f, "F{}", number),
+            (None, code) => write!(f, "{:?}", code),
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = ConfigError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let unknown = || ConfigError::UnknownKey { name: String::from(text) };
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        loop {
+            if let Some(after) = rest.strip_prefix("Ctrl+") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("Alt+") {
+                modifiers |= KeyModifiers::ALT;
+                rest = after;
+            } else {
+                break;
+            }
+        }
+
+        let mut characters = rest.chars();
+        let code = match (characters.next(), characters.next()) {
+            (Some(character), None) => KeyCode::Char(character),
+            _ => match KEY_NAMES.iter().find(|(name, _)| name.eq_ignore_ascii_case(rest)) {
+                Some((_, code)) => *code,
+                None => KeyCode::F(rest.strip_prefix('F').and_then(|n| n.parse().ok()).ok_or_else(unknown)?),
+            },
+        };
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl TryFrom<String> for Key {
+    type Error = ConfigError;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<Key> for String {
+    fn from(key: Key) -> Self {
+        key.to_string()
+    }
+}
+
+/// The bindings a player can change, in the order the settings screen lists them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+    PlayerOneUp,
+    PlayerOneDown,
+    PlayerTwoUp,
+    PlayerTwoDown,
+    Pause,
+    Quit,
+    Chat,
+    Rematch,
+}
+
+impl Binding {
+    pub const ALL: [Binding; 8] = [
+        Binding::PlayerOneUp,
+        Binding::PlayerOneDown,
+        Binding::PlayerTwoUp,
+        Binding::PlayerTwoDown,
+        Binding::Pause,
+        Binding::Quit,
+        Binding::Chat,
+        Binding::Rematch,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Binding::PlayerOneUp => "Player 1 up",
+            Binding::PlayerOneDown => "Player 1 down",
+            Binding::PlayerTwoUp => "Player 2 up",
+            Binding::PlayerTwoDown => "Player 2 down",
+            Binding::Pause => "Pause",
+            Binding::Quit => "Quit",
+            Binding::Chat => "Chat",
+            Binding::Rematch => "Rematch",
+        }
+    }
+
+    fn action(&self) -> Action {
+        match self {
+            Binding::PlayerOneUp | Binding::PlayerTwoUp => Action::Up,
+            Binding::PlayerOneDown | Binding::PlayerTwoDown => Action::Down,
+            Binding::Pause => Action::Pause,
+            Binding::Quit => Action::Quit,
+            Binding::Chat => Action::Chat,
+            Binding::Rematch => Action::Rematch,
+        }
+    }
+
+    fn side(&self) -> Option<Side> {
+        match self {
+            Binding::PlayerOneUp | Binding::PlayerOneDown => Some(Side::PlayerOne),
+            Binding::PlayerTwoUp | Binding::PlayerTwoDown => Some(Side::PlayerTwo),
+            Binding::Pause | Binding::Quit | Binding::Chat | Binding::Rematch => None,
+        }
+    }
+}
+
+/// Translates key presses into actions. Each binding may have several keys, e.g. `w`
+/// and `k` for player one's up. Playing online or against the computer, either player's
+/// keys move the one paddle the player controls.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InputMap {
+    pub player_one_up: Vec<Key>,
+    pub player_one_down: Vec<Key>,
+    pub player_two_up: Vec<Key>,
+    pub player_two_down: Vec<Key>,
+    pub pause: Vec<Key>,
+    pub quit: Vec<Key>,
+    pub chat: Vec<Key>,
+    pub rematch: Vec<Key>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self {
+            player_one_up: vec![Key::char('w')],
+            player_one_down: vec![Key::char('s')],
+            player_two_up: vec![Key::plain(KeyCode::Up)],
+            player_two_down: vec![Key::plain(KeyCode::Down)],
+            pause: vec![Key::char('p')],
+            quit: vec![Key::new(KeyCode::Char('c'), KeyModifiers::CONTROL), Key::plain(KeyCode::Esc)],
+            chat: vec![Key::char('t')],
+            rematch: vec![Key::char('r')],
+        }
+    }
+}
+
+impl InputMap {
+    pub fn keys(&self, binding: Binding) -> &[Key] {
+        match binding {
+            Binding::PlayerOneUp => &self.player_one_up,
+            Binding::PlayerOneDown => &self.player_one_down,
+            Binding::PlayerTwoUp => &self.player_two_up,
+            Binding::PlayerTwoDown => &self.player_two_down,
+            Binding::Pause => &self.pause,
+            Binding::Quit => &self.quit,
+            Binding::Chat => &self.chat,
+            Binding::Rematch => &self.rematch,
+        }
+    }
+
+    pub fn keys_mut(&mut self, binding: Binding) -> &mut Vec<Key> {
+        match binding {
+            Binding::PlayerOneUp => &mut self.player_one_up,
+            Binding::PlayerOneDown => &mut self.player_one_down,
+            Binding::PlayerTwoUp => &mut self.player_two_up,
+            Binding::PlayerTwoDown => &mut self.player_two_down,
+            Binding::Pause => &mut self.pause,
+            Binding::Quit => &mut self.quit,
+            Binding::Chat => &mut self.chat,
+            Binding::Rematch => &mut self.rematch,
+        }
+    }
+
+    /// Gives player one's movement keys to player two and the other way round, so each
+    /// player keeps their keys after switching ends.
+    pub fn swap_players(&mut self) {
+        std::mem::swap(&mut self.player_one_up, &mut self.player_two_up);
+        std::mem::swap(&mut self.player_one_down, &mut self.player_two_down);
+    }
+
+    /// The action `event` is bound to, and for paddle movement which player's key it is.
+    /// A key bound twice does what the binding listed first in `Binding::ALL` says.
+    /// Ctrl-C always quits, so a bad map can never leave the player stuck in a match.
+    pub fn lookup(&self, event: &KeyEvent) -> Option<(Action, Option<Side>)> {
+        self.lookup_key(Key::from_event(event))
+    }
+
+    fn lookup_key(&self, key: Key) -> Option<(Action, Option<Side>)> {
+        if key == Key::new(KeyCode::Char('c'), KeyModifiers::CONTROL) {
+            return Some((Action::Quit, None));
+        }
+        Binding::ALL
+            .into_iter()
+            .find(|binding| self.keys(*binding).contains(&key))
+            .map(|binding| (binding.action(), binding.side()))
+    }
+
+    /// Just the action `event` is bound to.
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        self.lookup(event).map(|(action, _)| action)
+    }
+}
+
+/// Keys held down right now, on terminals that report releases. A paddle keeps moving
+/// every tick for as long as its key is held, however fast the keyboard repeats.
+#[derive(Clone, Debug, Default)]
+pub struct HeldKeys {
+    keys: Vec<Key>,
+}
+
+impl HeldKeys {
+    pub fn update(&mut self, event: &KeyEvent) {
+        let key = Key::from_event(event);
+        match event.kind {
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                if !self.keys.contains(&key) {
+                    self.keys.push(key);
+                }
+            },
+            // the key may come back up with a different shift or modifier state than
+            // it went down with, which still releases it
+            KeyEventKind::Release => self.keys.retain(|held| !same_key(held.code, key.code)),
+        }
+    }
+
+    /// The way each player's held keys move their paddle. Holding up and down together
+    /// keeps the paddle still.
+    pub fn paddle_inputs(&self, input_map: &InputMap) -> [PaddleInput; 2] {
+        let mut up = [false; 2];
+        let mut down = [false; 2];
+        for key in &self.keys {
+            match input_map.lookup_key(*key) {
+                Some((Action::Up, Some(side))) => up[side as usize] = true,
+                Some((Action::Down, Some(side))) => down[side as usize] = true,
+                _ => {},
+            }
+        }
+        [0, 1].map(|side| match (up[side], down[side]) {
+            (true, false) => PaddleInput::Up,
+            (false, true) => PaddleInput::Down,
+            _ => PaddleInput::Idle,
+        })
+    }
+}
+
+fn same_key(held: KeyCode, released: KeyCode) -> bool {
+    match (held, released) {
+        (KeyCode::Char(held), KeyCode::Char(released)) => held.to_lowercase().eq(released.to_lowercase()),
+        _ => held == released,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_event(character: char, modifiers: KeyModifiers, kind: KeyEventKind) -> KeyEvent {
+        KeyEvent::new_with_kind(KeyCode::Char(character), modifiers, kind)
+    }
+
+    #[test]
+    fn a_paddle_moves_from_press_to_release() {
+        let input_map = InputMap::default();
+        let mut held = HeldKeys::default();
+        let idle = [PaddleInput::Idle; 2];
+
+        held.update(&key_event('w', KeyModifiers::NONE, KeyEventKind::Press));
+        assert_eq!(held.paddle_inputs(&input_map), [PaddleInput::Up, PaddleInput::Idle]);
+        held.update(&key_event('w', KeyModifiers::NONE, KeyEventKind::Repeat));
+        assert_eq!(held.paddle_inputs(&input_map), [PaddleInput::Up, PaddleInput::Idle]);
+        held.update(&key_event('w', KeyModifiers::NONE, KeyEventKind::Release));
+        assert_eq!(held.paddle_inputs(&input_map), idle);
+
+        // up and down together cancel out, letting go of one leaves the other
+        held.update(&key_event('w', KeyModifiers::NONE, KeyEventKind::Press));
+        held.update(&key_event('s', KeyModifiers::NONE, KeyEventKind::Press));
+        assert_eq!(held.paddle_inputs(&input_map), idle);
+        held.update(&key_event('w', KeyModifiers::NONE, KeyEventKind::Release));
+        assert_eq!(held.paddle_inputs(&input_map), [PaddleInput::Down, PaddleInput::Idle]);
+        held.update(&key_event('s', KeyModifiers::NONE, KeyEventKind::Release));
+        assert_eq!(held.paddle_inputs(&input_map), idle);
+    }
+
+    #[test]
+    fn a_key_released_with_shift_still_comes_up() {
+        let input_map = InputMap::default();
+        let mut held = HeldKeys::default();
+
+        held.update(&key_event('w', KeyModifiers::NONE, KeyEventKind::Press));
+        held.update(&key_event('W', KeyModifiers::SHIFT, KeyEventKind::Release));
+
+        assert_eq!(held.paddle_inputs(&input_map), [PaddleInput::Idle; 2]);
+    }
+
+    #[test]
+    fn keys_read_back_as_written() {
+        for text in ["w", "W", "Up", "Space", "PageDown", "F5", "Ctrl+c", "Alt+Enter", "Ctrl+Alt+x"] {
+            let key: Key = text.parse().unwrap();
+            assert_eq!(key.to_string(), text);
+        }
+        assert_eq!("space".parse::<Key>().unwrap(), Key::char(' '));
+        assert_eq!("Ctrl+Esc".parse::<Key>().unwrap(), Key::new(KeyCode::Esc, KeyModifiers::CONTROL));
+        for text in ["", "Ctrl+", "Escape", "Fx"] {
+            assert!(matches!(text.parse::<Key>(), Err(ConfigError::UnknownKey { .. })), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn keys_look_up_their_binding() {
+        let mut input_map = InputMap::default();
+        let press = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        assert_eq!(input_map.lookup(&press(KeyCode::Char('w'))), Some((Action::Up, Some(Side::PlayerOne))));
+        assert_eq!(input_map.lookup(&press(KeyCode::Down)), Some((Action::Down, Some(Side::PlayerTwo))));
+        assert_eq!(input_map.lookup(&press(KeyCode::Char('p'))), Some((Action::Pause, None)));
+        assert_eq!(input_map.lookup(&press(KeyCode::Char('x'))), None);
+
+        // a key bound twice does what the earlier binding says
+        input_map.pause.push(Key::char('w'));
+        assert_eq!(input_map.action(&press(KeyCode::Char('w'))), Some(Action::Up));
+    }
+
+    #[test]
+    fn ctrl_c_always_quits() {
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        let mut input_map = InputMap::default();
+        input_map.quit.clear();
+        input_map.player_one_up = vec![Key::new(KeyCode::Char('c'), KeyModifiers::CONTROL)];
+        assert_eq!(input_map.lookup(&ctrl_c), Some((Action::Quit, None)));
+    }
+}
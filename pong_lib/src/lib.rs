@@ -1,35 +1,130 @@
-use std::io::{self, stdout, Write, Read};
-use std::net::TcpStream;
-use std::process;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, MediaKeyCode};
-use crossterm::{
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen}, 
-    ExecutableCommand
-};
-use ratatui::layout::{Layout, self, Constraint, Rect};
+pub mod ai;
+pub mod chat;
+pub mod collision;
+pub mod config;
+mod effects;
+mod hud;
+pub mod error;
+pub mod events;
+pub mod history;
+pub mod input;
+pub mod matchmaking;
+pub mod menu;
+pub mod pause;
+pub mod protocol;
+pub mod rating;
+pub mod rematch;
+pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod rules;
+pub mod serve;
+pub mod settings;
+pub mod terminal;
+pub mod theme;
+mod view;
+
+use std::io::{self, stdout};
+use std::path::{Path, PathBuf};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, MouseEventKind};
+use ratatui::layout::Rect;
 use ratatui::style::Style;
 use ratatui::{
     prelude::{CrosstermBackend, Terminal, *},
     widgets::*,
-    Frame
 }; 
-use std::str;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use std::{fmt, thread};
 use serde::{Deserialize, Serialize};
-use serde_json::{Result, Value, json};
+use ai::{CpuPlayer, Difficulty};
+use chat::{ChatLimiter, ChatLine, ChatPanel};
+use collision::{sweep_point, Aabb, Vec2};
+use config::{validate_arena, ArenaConfig, ConfigError};
+use error::PongError;
+use effects::Effects;
+use hud::RallyStats;
+use events::{GameEvent, Wall};
+use input::{Action, Binding, HeldKeys, InputMap, Key};
+use pause::PauseState;
+use rematch::RematchVote;
+use protocol::{send_message, welcome_client, ClientMessage, MessageReader, NetStats, PlayerInfo, ServerConnection, ServerMessage};
+use render::{Renderer, Shape};
+use history::{MatchHistory, MatchRecord, PlayerStats};
+use replay::{default_replay_path, Replay};
+use rng::SimRng;
+use rules::{MatchEnd, MatchResult, MatchRules, MatchStats};
+use serve::{ServeRules, ServeState};
+use settings::Settings;
+use terminal::TerminalGuard;
+use theme::Theme;
+use view::ArenaView;
+
+/// Upper bound on how many surfaces the ball may bounce off within a single tick.
+const MAX_BOUNCES_PER_TICK: usize = 4;
+
+/// The game loops poll for input every 50ms, so the simulation advances 20 times a second.
+pub const TICKS_PER_SECOND: u16 = 20;
+
+/// How long a spectator may block the server on a write before it is disconnected.
+const SPECTATOR_WRITE_TIMEOUT: Duration = Duration::from_millis(100);
 
+/// Columns the chat panel takes, borders included.
+const CHAT_PANEL_WIDTH: u16 = 28;
 
-#[derive(Deserialize, Serialize, Debug)]
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct GameDataJSON {
     player_one_pos: [u16; 2],
     player_two_pos: [u16; 2],
-    pong_pos: [u16; 2],
+    pong_pos: [f32; 2],
     player_one_score: u16,
     player_two_score: u16,
+    /// Ticks until the next serve, 0 while the ball is in play.
+    serve_countdown: u16,
+    player_one_games: u16,
+    player_two_games: u16,
+    elapsed_ticks: u32,
+    time_limit_secs: Option<u16>,
+    match_result: Option<MatchResult>,
+    /// Events from the tick that produced this snapshot.
+    events: Vec<GameEvent>,
+    pause: PauseState,
+    /// Who is playing, player one first.
+    players: [PlayerInfo; 2],
+    /// Whether the players want to play again, once the match is over.
+    rematch: RematchVote,
+}
+
+/// Which end of the arena a player defends. Player one is on the left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Side {
+    PlayerOne,
+    PlayerTwo,
+}
+
+impl Side {
+    pub fn opposite(self) -> Self {
+        match self {
+            Side::PlayerOne => Side::PlayerTwo,
+            Side::PlayerTwo => Side::PlayerOne,
+        }
+    }
+}
+
+/// What a player does with their paddle during one tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PaddleInput {
+    #[default]
+    Idle,
+    Up,
+    Down,
 }
 
 struct Player {
     score: u16,
+    games: u16,
     x: u16,
     y: u16,
 }
@@ -37,7 +132,7 @@ struct Player {
 impl Player {
     fn new(x: u16, y: u16) -> Self {
         Self {
-            score: 0, x, y
+            score: 0, games: 0, x, y
         }
     }
 
@@ -47,31 +142,37 @@ impl Player {
 }
 
 struct PongBall {
-    dimensions: Rect,
-    velocity: (i16, i16),
+    position: Vec2,
+    size: (u16, u16),
+    velocity: Vec2,
 }
 
 impl PongBall {
-    fn new(dimensions: Rect) -> Self {
+    fn new(x: u16, y: u16) -> Self {
         Self {
-            dimensions,
-            velocity: (1, 1),
+            position: Vec2::new(x as f32, y as f32),
+            size: (1, 1),
+            velocity: Vec2::ZERO,
         }
     }
 
-    fn switch_x_direction(&mut self) {
-        self.velocity.0 *= -1;
+    pub fn get_pong_pos(&self) -> [f32; 2] {
+        [self.position.x, self.position.y]
     }
 
-    pub fn get_pong_pos(&self) -> [u16; 2] {
-        [self.dimensions.x, self.dimensions.y]
+    pub fn set_pong_pos(&mut self, pong_pos: [f32; 2]) {
+        self.position = Vec2::new(pong_pos[0], pong_pos[1]);
     }
 
-    pub fn set_pong_pos(&mut self, pong_pos: [u16; 2]) {
-        self.dimensions.x = pong_pos[0];
-        self.dimensions.y = pong_pos[1];
+    /// Terminal cell the ball is drawn in.
+    fn get_rect(&self) -> Rect {
+        Rect::new(
+            self.position.x.round() as u16,
+            self.position.y.round() as u16,
+            self.size.0,
+            self.size.1,
+        )
     }
-
 }
 
 struct GameState {
@@ -80,15 +181,28 @@ struct GameState {
     pong_ball: PongBall,
     dimensions: Rect,
     paddle_size: (u16, u16),
+    /// Rows a paddle moves per tick.
+    paddle_speed: u16,
+    serve_rules: ServeRules,
+    serve: ServeState,
+    last_receiver: Side,
+    match_rules: MatchRules,
+    match_result: Option<MatchResult>,
+    elapsed_ticks: u32,
+    events: Vec<GameEvent>,
+    stats: MatchStats,
+    seed: u64,
+    rng: SimRng,
 }
 
 impl GameState {
-    fn new(dimensions: Rect) -> Self {
+    fn new(dimensions: Rect, paddle_size: (u16, u16), seed: u64) -> Result<Self, ConfigError> {
+        validate_arena(dimensions, paddle_size)?;
+
         let Rect { x, y, width, height } = dimensions;
-        let paddle_size = (2, 4);
-        let paddle_y_start_pos= ((y + height) / 2 - (paddle_size.1 / 2)) as u16;
+        let paddle_y_start_pos= y + height / 2 - paddle_size.1 / 2;
 
-        Self {
+        Ok(Self {
             player: Player::new(
                 x + 1, 
                 paddle_y_start_pos,
@@ -97,15 +211,123 @@ impl GameState {
                 (x + width) - (paddle_size.0 + 1), 
                 paddle_y_start_pos,
             ),
-            pong_ball: PongBall::new(Rect::new(
-                (x + width) / 2 as u16, 
-                (y + height) / 2 as u16, 
-                1, 
-                1,
-            )),
-            dimensions: dimensions, 
-            paddle_size: (2, 4),
+            pong_ball: PongBall::new(
+                x + width / 2, 
+                y + height / 2, 
+            ),
+            dimensions, 
+            paddle_size,
+            paddle_speed: 1,
+            serve_rules: ServeRules::default(),
+            serve: ServeState::Countdown {
+                ticks_left: ServeRules::default().delay_ticks,
+                toward: Side::PlayerTwo,
+            },
+            last_receiver: Side::PlayerTwo,
+            match_rules: MatchRules::default(),
+            match_result: None,
+            elapsed_ticks: 0,
+            events: Vec::new(),
+            stats: MatchStats::default(),
+            seed,
+            rng: SimRng::new(seed),
+        })
+    }
+
+    /// Advances the simulation by one tick. Everything that happens is decided by the
+    /// seed and the inputs passed here, so replaying them reproduces the match.
+    fn tick(&mut self, player_one: PaddleInput, player_two: PaddleInput) {
+        self.move_paddle(Side::PlayerOne, player_one);
+        self.move_paddle(Side::PlayerTwo, player_two);
+        self.move_pong_ball();
+    }
+
+    fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    // reports `event` to frontends and counts it towards the match stats
+    fn push_event(&mut self, event: GameEvent) {
+        self.stats.record(&event);
+        self.events.push(event);
+    }
+
+    // starts a new match on the same arena with the same rules, serving with a seed drawn
+    // from this match's so a replay of either can be rebuilt on its own
+    fn restart(&mut self) -> Result<(), ConfigError> {
+        let mut restarted = GameState::new(self.dimensions, self.paddle_size, self.rng.next_u64())?;
+        restarted.paddle_speed = self.paddle_speed;
+        restarted.serve_rules = self.serve_rules;
+        restarted.serve = ServeState::Countdown { ticks_left: self.serve_rules.delay_ticks, toward: restarted.last_receiver };
+        restarted.match_rules = self.match_rules;
+        *self = restarted;
+        Ok(())
+    }
+
+    // ends the match in the other player's favour, does nothing once it is already over
+    fn forfeit(&mut self, side: Side) {
+        if self.match_result.is_none() {
+            self.finish_match(MatchEnd::Forfeit(side));
+        }
+    }
+
+    fn get_player(&mut self, side: Side) -> &mut Player {
+        match side {
+            Side::PlayerOne => &mut self.player,
+            Side::PlayerTwo => &mut self.opponent,
+        }
+    }
+
+    fn award_point(&mut self, scorer: Side) {
+        let player = self.get_player(scorer);
+        player.score = player.score.saturating_add(1);
+        self.push_event(GameEvent::PointScored { scorer });
+
+        if let Some(winner) = self.match_rules.game_winner(self.player.score, self.opponent.score) {
+            let player = self.get_player(winner);
+            player.games = player.games.saturating_add(1);
+            self.push_event(GameEvent::GameWon {
+                winner,
+                player_one_games: self.player.games,
+                player_two_games: self.opponent.games,
+            });
+
+            if self.get_player(winner).games >= self.match_rules.games_to_win() {
+                self.finish_match(MatchEnd::GamesWon);
+                return;
+            }
+
+            self.player.score = 0;
+            self.opponent.score = 0;
         }
+
+        self.start_serve(scorer.opposite());
+    }
+
+    fn finish_match(&mut self, reason: MatchEnd) {
+        let games = (self.player.games, self.opponent.games);
+        let points = (self.player.score, self.opponent.score);
+        // on time the player ahead in games wins, then the player ahead in the current game
+        let winner = match (reason, games.cmp(&(games.1, games.0)).then(points.0.cmp(&points.1))) {
+            (MatchEnd::Forfeit(side), _) => Some(side.opposite()),
+            (_, std::cmp::Ordering::Greater) => Some(Side::PlayerOne),
+            (_, std::cmp::Ordering::Less) => Some(Side::PlayerTwo),
+            (_, std::cmp::Ordering::Equal) => None,
+        };
+
+        let result = MatchResult {
+            winner,
+            reason,
+            player_one_games: games.0,
+            player_two_games: games.1,
+            player_one_score: points.0,
+            player_two_score: points.1,
+            elapsed_ticks: self.elapsed_ticks,
+            stats: self.stats,
+        };
+        self.match_result = Some(result);
+        self.push_event(GameEvent::MatchOver(result));
+        self.reset_pong_position();
     }
 
     fn get_player_paddle_pos(&self) -> [u16; 2] {
@@ -139,130 +361,513 @@ impl GameState {
         (y, y + height, x, x + width)
     }
 
-    fn get_pong_pos(&mut self) -> [u16; 2] {
-        [self.pong_ball.dimensions.x, self.pong_ball.dimensions.y]
-    }
-
     fn reset_pong_position(&mut self) {
         let Rect { x, y, width, height}  = self.dimensions;
-        self.pong_ball.dimensions = Rect::new((x + width) / 2 as u16, (y + height) / 2 as u16, 1, 1);
+        self.pong_ball = PongBall::new(x + width / 2, y + height / 2);
     }
 
-    fn move_pong_ball(&mut self) {
-        // update ball position
-        let Rect { x, y, .. } = self.pong_ball.dimensions;
+    // park the ball at the centre and start the countdown to the next serve
+    fn start_serve(&mut self, conceder: Side) {
+        let toward = self.serve_rules.next_receiver(conceder, self.last_receiver);
+        self.reset_pong_position();
+        self.serve = ServeState::Countdown { ticks_left: self.serve_rules.delay_ticks, toward };
+    }
 
-        let (
-            borderTop, 
-            borderBottom, 
-            borderLeft, 
-            borderRight
-        ) = self.get_game_borders(); 
+    // counts down the serve delay, returns true once the ball is in play
+    fn advance_serve(&mut self) -> bool {
+        match self.serve {
+            ServeState::Countdown { ticks_left, toward } if ticks_left > 1 => {
+                self.serve = ServeState::Countdown { ticks_left: ticks_left - 1, toward };
+                false
+            },
+            ServeState::Countdown { toward, .. } => {
+                self.pong_ball.velocity = self.serve_rules.serve_velocity(toward, &mut self.rng);
+                self.last_receiver = toward;
+                self.serve = ServeState::InPlay;
+                true
+            },
+            ServeState::InPlay => true,
+        }
+    }
 
+    fn get_paddle_rects(&self) -> [Rect; 2] {
+        let (paddle_width, paddle_height) = self.paddle_size;
+        [
+            Rect::new(self.player.x, self.player.y, paddle_width, paddle_height),
+            Rect::new(self.opponent.x, self.opponent.y, paddle_width, paddle_height),
+        ]
+    }
 
-        if y <= borderTop + 1 || y >= borderBottom - 1 {
-            self.pong_ball.velocity.1 *= -1;
-        } 
+    // every surface the ball can bounce off, grown by the ball's size so the ball
+    // can be swept as a point
+    fn get_obstacles(&self) -> [Aabb; 4] {
+        let Rect { x, y, width, height } = self.dimensions;
+        let ball_size = Vec2::new(self.pong_ball.size.0 as f32, self.pong_ball.size.1 as f32);
+        let (left, top) = (x as f32, y as f32);
+        let (width, height) = (width as f32, height as f32);
+        let [player_paddle, opponent_paddle] = self.get_paddle_rects();
 
-        let player_paddle = self.get_player_paddle_pos();
-        let opponent_paddle = self.get_opponent_paddle_pos();
+        let top_wall = Aabb::new(
+            Vec2::new(left - width, top - height),
+            Vec2::new(left + 2.0 * width, top + 1.0),
+        );
+        let bottom_wall = Aabb::new(
+            Vec2::new(left - width, top + height - 1.0),
+            Vec2::new(left + 2.0 * width, top + 2.0 * height),
+        );
 
-        if x == player_paddle[0] + self.paddle_size.0 && 
-            y >= player_paddle[1] && 
-            y <= (player_paddle[1] + self.paddle_size.1) {
-                self.pong_ball.switch_x_direction();
+        [
+            top_wall.expand(ball_size),
+            bottom_wall.expand(ball_size),
+            Aabb::from_rect(player_paddle).expand(ball_size),
+            Aabb::from_rect(opponent_paddle).expand(ball_size),
+        ]
+    }
+
+    // a paddle that moved into the ball pushes it out of the paddle's front face
+    fn resolve_paddle_overlap(&mut self, position: &mut Vec2, velocity: &mut Vec2) {
+        let [_, _, player_paddle, opponent_paddle] = self.get_obstacles();
+
+        if player_paddle.contains(*position) {
+            position.x = player_paddle.max.x;
+            velocity.x = velocity.x.abs();
+            self.push_event(GameEvent::PaddleHit { side: Side::PlayerOne });
+        }
+
+        if opponent_paddle.contains(*position) {
+            position.x = opponent_paddle.min.x;
+            velocity.x = -velocity.x.abs();
+            self.push_event(GameEvent::PaddleHit { side: Side::PlayerTwo });
+        }
+    }
+
+    // what the ball at `position` bouncing off `get_obstacles()[surface]` looks like
+    fn bounce_event(&self, surface: usize, position: Vec2) -> GameEvent {
+        let x = (position.x + self.pong_ball.size.0 as f32 / 2.0) as u16;
+        match surface {
+            0 => GameEvent::WallBounce { wall: Wall::Top, x },
+            1 => GameEvent::WallBounce { wall: Wall::Bottom, x },
+            2 => GameEvent::PaddleHit { side: Side::PlayerOne },
+            _ => GameEvent::PaddleHit { side: Side::PlayerTwo },
         }
-         
-        if x == opponent_paddle[0] - 1 && 
-            y >= opponent_paddle[1] && 
-            y <= (opponent_paddle[1] + self.paddle_size.1) {
-                self.pong_ball.switch_x_direction();
+    }
+
+    fn move_pong_ball(&mut self) {
+        if self.match_result.is_some() {
+            return;
         }
 
-        if x <= self.dimensions.x {
-            self.opponent.score += 1;
-            self.pong_ball.switch_x_direction();
-            self.reset_pong_position();
+        self.elapsed_ticks = self.elapsed_ticks.saturating_add(1);
+        if let Some(limit) = self.match_rules.time_limit_ticks() {
+            if self.elapsed_ticks >= limit {
+                self.finish_match(MatchEnd::TimeLimit);
+                return;
+            }
         }
 
-        if x >= self.dimensions.x + self.dimensions.width - 1 {
-            self.player.score += 1;
-            self.pong_ball.switch_x_direction();
-            self.reset_pong_position();
+        if !self.advance_serve() {
+            return;
         }
 
-        if self.pong_ball.velocity.1 < 0 {
-            self.pong_ball.dimensions.y -= 1;
-        } else {
-            self.pong_ball.dimensions.y += 1;
+        let mut position = self.pong_ball.position;
+        let mut velocity = self.pong_ball.velocity;
+
+        self.resolve_paddle_overlap(&mut position, &mut velocity);
+
+        // sweep the ball along its path for this tick, bouncing off whichever
+        // surface it reaches first until the movement is used up
+        let obstacles = self.get_obstacles();
+        let mut remaining = 1.0;
+        for _ in 0..MAX_BOUNCES_PER_TICK {
+            let delta = velocity * remaining;
+            let first_hit = obstacles
+                .iter()
+                .enumerate()
+                .filter_map(|(surface, obstacle)| sweep_point(position, delta, obstacle).map(|hit| (surface, hit)))
+                .min_by(|(_, a), (_, b)| a.time.total_cmp(&b.time));
+
+            match first_hit {
+                Some((surface, hit)) => {
+                    position = position + delta * hit.time;
+                    velocity = velocity.reflect(hit.normal);
+                    remaining *= 1.0 - hit.time;
+                    let event = self.bounce_event(surface, position);
+                    self.push_event(event);
+                },
+                None => {
+                    position = position + delta;
+                    break;
+                },
+            }
         }
-        if self.pong_ball.velocity.0 < 0 {
-            self.pong_ball.dimensions.x -= 1;
+
+        self.pong_ball.velocity = velocity;
+
+        let (border_top, border_bottom, border_left, border_right) = self.get_game_borders();
+
+        if position.x <= border_left as f32 {
+            self.award_point(Side::PlayerTwo);
+        } else if position.x >= (border_right - 1) as f32 {
+            self.award_point(Side::PlayerOne);
         } else {
-            self.pong_ball.dimensions.x += 1;
+            // the clamp keeps the ball off the borders in case a bounce left it on one
+            self.pong_ball.position = Vec2::new(
+                position.x.clamp((border_left + 1) as f32, (border_right - 2) as f32),
+                position.y.clamp((border_top + 1) as f32, (border_bottom - 2) as f32),
+            );
         }
+
+        debug_assert!(self.is_within_arena(), "pong ball left the arena");
     }
 
-    fn move_paddle(&mut self, key: KeyCode) {
-        let Rect { x, y, width, height } = self.dimensions;
-        let (
-            borderTop, 
-            borderBottom, 
-            borderLeft, 
-            borderRight
-        ) = self.get_game_borders(); 
-
-        match key {
-            KeyCode::Char('w') => {
-                if self.player.y - 1 > borderTop {
-                    self.player.y -= 1;
-                }
-            },
-            KeyCode::Char('s') => {
-                if (self.player.y + self.paddle_size.1) + 1 < borderBottom {
-                    self.player.y += 1;
-                }
-            },
-            KeyCode::Up => {
-                if self.opponent.y - 1 > borderTop {
-                    self.opponent.y -= 1;
-                }
+    // the ball and both paddles sit strictly inside the arena's border
+    fn is_within_arena(&self) -> bool {
+        let (border_top, border_bottom, border_left, border_right) = self.get_game_borders();
+        let ball = self.pong_ball.get_rect();
+        let ball_inside = ball.x > border_left && ball.right() < border_right &&
+            ball.y > border_top && ball.bottom() < border_bottom;
+
+        ball_inside && self.get_paddle_rects().iter().all(|paddle| {
+            paddle.x > border_left && paddle.right() < border_right &&
+                paddle.y > border_top && paddle.bottom() < border_bottom
+        })
+    }
+
+    fn move_paddle(&mut self, side: Side, input: PaddleInput) {
+        if self.match_result.is_some() {
+            return;
+        }
+
+        let (border_top, border_bottom, _, _) = self.get_game_borders(); 
+        let paddle_height = self.paddle_size.1;
+        let paddle_speed = self.paddle_speed;
+        let paddle = self.get_player(side);
+
+        // a fast paddle stops against the border rather than short of it
+        match input {
+            PaddleInput::Up => {
+                let room = paddle.y.saturating_sub(border_top + 1);
+                paddle.y -= paddle_speed.min(room);
             },
-            KeyCode::Down => {
-                if (self.opponent.y + self.paddle_size.1) + 1 < borderBottom {
-                    self.opponent.y += 1;
-                }
+            PaddleInput::Down => {
+                let room = border_bottom.saturating_sub(paddle.y + paddle_height + 1);
+                paddle.y += paddle_speed.min(room);
             },
-            _ => {}
+            PaddleInput::Idle => {}
         }
+
+        debug_assert!(self.is_within_arena(), "paddle left the arena");
     }
 }
 
+/// What the keyboard asked for during one tick.
+#[derive(Clone, Copy, Debug, Default)]
+struct TickKeys {
+    inputs: [PaddleInput; 2],
+    pause: bool,
+    quit: bool,
+    rematch: bool,
+}
+
+/// How this terminal takes part in the match, which decides what the results screen
+/// offers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Seat {
+    /// Both players at this keyboard, or one against the computer.
+    Local,
+    /// One of the players in a networked match.
+    Player,
+    Spectator,
+}
+
+/// Whoever sent the server a chat message.
+#[derive(Clone, Copy, Debug)]
+enum ChatSender {
+    Player(Side),
+    /// A spectator, known by the address they connected from.
+    Spectator(SocketAddr),
+}
+
+/// Someone watching a match the server runs.
+struct Spectator {
+    stream: TcpStream,
+    address: SocketAddr,
+    name: String,
+    chat_limiter: ChatLimiter,
+}
+
 pub struct TerminalOutput {
-    game_state: GameState
+    game_state: GameState,
+    recording: Option<Replay>,
+    pause: PauseState,
+    /// Text shown along the bottom border of the arena.
+    footer: Option<String>,
+    /// Whether `run_server` prints what it is doing.
+    logging: bool,
+    /// Shortest time between two frames.
+    frame_interval: Duration,
+    last_frame: Option<Instant>,
+    /// What the keys do.
+    input_map: InputMap,
+    /// Whether the player's paddle follows the mouse pointer.
+    mouse_control: bool,
+    /// Screen row the mouse pointer was last seen on.
+    mouse_row: Option<u16>,
+    renderer: Renderer,
+    theme: Theme,
+    effects: Effects,
+    /// Whether `effects` are drawn.
+    show_effects: bool,
+    /// Who is playing, player one first.
+    players: [PlayerInfo; 2],
+    rally_stats: RallyStats,
+    /// How the connection is doing, while playing online.
+    net_stats: Option<NetStats>,
+    /// The chat, in games played or watched over the network.
+    chat: Option<ChatPanel>,
+    seat: Seat,
+    rematch: RematchVote,
+    /// Where finished matches are kept, on a server.
+    history: Option<MatchHistory>,
+    /// Both players' totals from the server's history, once the match is over.
+    career: Option<[PlayerStats; 2]>,
+    /// Where the replay of the last match finished went.
+    saved_replay: Option<PathBuf>,
 }
 
 
 impl TerminalOutput {
-    pub fn new(width: u16, height: u16) -> Self {
-        let dimensions = Rect::new(0, 0, width, height);
-        Self {
-            game_state: GameState::new(dimensions),
+    pub fn new(arena: ArenaConfig, seed: u64) -> Result<Self, PongError> {
+        arena.validate()?;
+        let mut game_state = GameState::new(arena.dimensions(), arena.paddle_size(), seed)?;
+        game_state.paddle_speed = arena.paddle_speed;
+        Ok(Self {
+            game_state,
+            recording: None,
+            pause: PauseState::Running,
+            footer: None,
+            logging: false,
+            frame_interval: Duration::from_secs(1) / TICKS_PER_SECOND as u32,
+            last_frame: None,
+            input_map: InputMap::default(),
+            mouse_control: false,
+            mouse_row: None,
+            renderer: Renderer::default(),
+            theme: Theme::default(),
+            effects: Effects::default(),
+            show_effects: true,
+            players: [PlayerInfo::new("Player 1"), PlayerInfo::new("Player 2")],
+            rally_stats: RallyStats::default(),
+            net_stats: None,
+            chat: None,
+            seat: Seat::Local,
+            rematch: RematchVote::default(),
+            history: None,
+            career: None,
+            saved_replay: None,
+        })
+    }
+
+    /// Runs one tick of the simulation and returns the resulting snapshot along with
+    /// the events that tick produced.
+    pub fn step(&mut self, player_one: PaddleInput, player_two: PaddleInput) -> GameDataJSON {
+        self.game_state.tick(player_one, player_two);
+        let game_data = self.snapshot();
+        if let Some(recording) = &mut self.recording {
+            recording.record([player_one, player_two], &game_data);
         }
+        game_data
+    }
+
+    // the current state along with any events not yet reported
+    fn snapshot(&mut self) -> GameDataJSON {
+        let mut game_data = self.get_game_data();
+        game_data.events = self.game_state.take_events();
+        self.effects.update(self.game_state.pong_ball.position, &game_data.events);
+        self.rally_stats.update(self.game_state.pong_ball.position, &game_data.events);
+        game_data
+    }
+
+    // ends the match in the other player's favour between ticks, so the recording notes
+    // it for playback to apply after its last input
+    fn forfeit(&mut self, side: Side) {
+        if self.game_state.match_result.is_some() {
+            return;
+        }
+        self.game_state.forfeit(side);
+        if let Some(recording) = &mut self.recording {
+            recording.forfeit = Some(side);
+        }
+    }
+
+    /// Records every following `step` into a replay. Call it after setting the rules
+    /// and players and before the first tick.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Replay::new(
+            self.game_state.seed,
+            self.arena(),
+            self.game_state.serve_rules,
+            self.game_state.match_rules,
+            self.players.clone(),
+        ));
+    }
+
+    pub fn take_recording(&mut self) -> Option<Replay> {
+        self.recording.take()
+    }
+
+    /// Where the replay of the last match finished went, if it was recorded. Replays
+    /// are saved as each match ends.
+    pub fn saved_replay(&self) -> Option<&Path> {
+        self.saved_replay.as_deref()
+    }
+
+    /// Adds every match `run_server` finishes to `history`, and sends the players their
+    /// totals from it at the end of each.
+    pub fn set_history(&mut self, history: MatchHistory) {
+        self.history = Some(history);
+    }
+
+    // saves the replay of the match just over, if recording
+    fn save_replay(&mut self) -> Result<(), PongError> {
+        self.saved_replay = None;
+        if let Some(replay) = &self.recording {
+            let replay_path = default_replay_path(replay.seed);
+            replay.save(&replay_path)?;
+            self.log(format_args!("Replay saved to {}", replay_path.display()));
+            self.saved_replay = Some(replay_path);
+        }
+        Ok(())
+    }
+
+    // adds the match just over to the history, if keeping one, along with where its
+    // replay went. Returns both players' totals including it.
+    fn record_match(&self) -> Result<Option<[PlayerStats; 2]>, PongError> {
+        let (Some(history), Some(result)) = (&self.history, self.game_state.match_result) else {
+            return Ok(None);
+        };
+        let names = self.players.clone().map(|player| player.name);
+        history.append(&MatchRecord::new(names.clone(), result, self.saved_replay.clone()))?;
+        let players = history.player_stats()?;
+        Ok(Some(names.map(|name| {
+            players.iter().find(|player| player.name == name).cloned().unwrap_or_else(|| PlayerStats::new(name))
+        })))
+    }
+
+    /// Prints every message and event to stdout while running a server.
+    pub fn set_logging(&mut self, enabled: bool) {
+        self.logging = enabled;
+    }
+
+    /// Caps how many times a second the game is redrawn, at most once per tick.
+    pub fn set_frame_rate(&mut self, frames_per_second: u16) {
+        self.frame_interval = Duration::from_secs(1) / frames_per_second.max(1) as u32;
+    }
+
+    pub fn set_input_map(&mut self, input_map: InputMap) {
+        self.input_map = input_map;
+    }
+
+    /// Lets the mouse drive the player's paddle, which heads for the row under the
+    /// pointer at full speed. A movement key held at the same time wins.
+    pub fn set_mouse_control(&mut self, enabled: bool) {
+        self.mouse_control = enabled;
+    }
+
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Draws a trail behind the ball, flashes where it hits and an animation for each
+    /// point scored.
+    pub fn set_effects(&mut self, enabled: bool) {
+        self.show_effects = enabled;
+    }
+
+    /// Takes on the player's preferences: frame rate, keys, mouse control, graphics,
+    /// theme and effects.
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.set_frame_rate(settings.frame_rate);
+        self.set_input_map(settings.keys.clone());
+        self.set_mouse_control(settings.mouse_control);
+        self.set_renderer(settings.renderer);
+        self.set_theme(settings.theme());
+        self.set_effects(settings.effects);
+    }
+
+    /// Who is playing, player one first. A server sends them to everyone with each state.
+    /// Player two is renamed if they share player one's name.
+    pub fn set_players(&mut self, players: [PlayerInfo; 2]) {
+        let [player_one, player_two] = players;
+        let player_two = player_two.distinct_from(&player_one);
+        self.players = [player_one, player_two];
+    }
+
+    pub fn players(&self) -> &[PlayerInfo; 2] {
+        &self.players
+    }
+
+    // starts the match over with the players' sides swapped, recording the new one if
+    // the last was
+    fn rematch(&mut self) -> Result<(), PongError> {
+        self.game_state.restart()?;
+        self.players.swap(0, 1);
+        self.pause = PauseState::Running;
+        self.rematch = RematchVote::default();
+        self.effects = Effects::default();
+        self.rally_stats = RallyStats::default();
+        self.career = None;
+        if self.recording.is_some() {
+            self.start_recording();
+        }
+        Ok(())
+    }
+
+    // the players' names, player one's first
+    fn names(&self) -> [&str; 2] {
+        [self.players[0].name.as_str(), self.players[1].name.as_str()]
+    }
+
+    // the colour a player picked, otherwise the theme's
+    fn player_color(&self, side: Side) -> Color {
+        self.players[side as usize].color.unwrap_or(self.theme.player(side))
+    }
+
+    pub fn arena(&self) -> ArenaConfig {
+        let Rect { width, height, .. } = self.game_state.dimensions;
+        let (paddle_width, paddle_height) = self.game_state.paddle_size;
+        ArenaConfig { width, height, paddle_width, paddle_height, paddle_speed: self.game_state.paddle_speed }
     }
 
     pub fn get_game_data(&self) -> GameDataJSON {
         let player_one_pos = self.game_state.get_player_paddle_pos();
         let player_two_pos = self.game_state.get_opponent_paddle_pos();
         let pong_pos = self.game_state.pong_ball.get_pong_pos();
-        let game_data = GameDataJSON {
+        let serve_countdown = match self.game_state.serve {
+            ServeState::Countdown { ticks_left, .. } => ticks_left,
+            ServeState::InPlay => 0,
+        };
+        GameDataJSON {
             player_one_pos,
             player_two_pos,
             pong_pos,
             player_one_score: self.game_state.player.score,
-            player_two_score: self.game_state.opponent.score
-        };
-        game_data
+            player_two_score: self.game_state.opponent.score,
+            serve_countdown,
+            player_one_games: self.game_state.player.games,
+            player_two_games: self.game_state.opponent.games,
+            elapsed_ticks: self.game_state.elapsed_ticks,
+            time_limit_secs: self.game_state.match_rules.time_limit_secs,
+            match_result: self.game_state.match_result,
+            events: Vec::new(),
+            pause: self.pause,
+            players: self.players.clone(),
+            rematch: self.rematch,
+        }
     }
 
     pub fn set_game_data(&mut self, game_data: &GameDataJSON) {
@@ -271,266 +876,1277 @@ impl TerminalOutput {
         self.game_state.pong_ball.set_pong_pos(game_data.pong_pos);
         self.game_state.set_player_score(game_data.player_one_score);
         self.game_state.set_opponent_score(game_data.player_two_score);
+        self.game_state.serve = match game_data.serve_countdown {
+            0 => ServeState::InPlay,
+            ticks_left => ServeState::Countdown { ticks_left, toward: self.game_state.last_receiver },
+        };
+        self.game_state.player.games = game_data.player_one_games;
+        self.game_state.opponent.games = game_data.player_two_games;
+        self.game_state.elapsed_ticks = game_data.elapsed_ticks;
+        self.game_state.match_rules.time_limit_secs = game_data.time_limit_secs;
+        self.game_state.match_result = game_data.match_result;
+        self.pause = game_data.pause;
+        self.players = game_data.players.clone();
+        self.rematch = game_data.rematch;
+        self.effects.update(self.game_state.pong_ball.position, &game_data.events);
+        self.rally_stats.update(self.game_state.pong_ball.position, &game_data.events);
+    }
+
+    // shows a chat line, if this game has a chat
+    pub(crate) fn receive_chat(&mut self, line: ChatLine) {
+        if let Some(chat) = &mut self.chat {
+            chat.receive(line);
+        }
+    }
+
+    // waits for the tick's state, taking in the chat that comes ahead of it
+    fn receive_state(&mut self, connection: &mut ServerConnection) -> Result<(), PongError> {
+        loop {
+            match connection.receive()? {
+                ServerMessage::State(game_data) => {
+                    self.set_game_data(&game_data);
+                    return Ok(());
+                },
+                ServerMessage::Chat(line) => self.receive_chat(line),
+                // the connection has switched sides already, what is left of the last
+                // match's effects goes
+                ServerMessage::Rematch(_) => {
+                    self.effects = Effects::default();
+                    self.rally_stats = RallyStats::default();
+                    self.career = None;
+                },
+                ServerMessage::PlayerStats(career) => self.career = Some(career),
+                message => return Err(PongError::Protocol(format!("expected a state, got {:?}", message))),
+            }
+        }
+    }
+
+    // sends the chat messages typed since the last tick
+    fn send_chat(&mut self, connection: &mut ServerConnection) -> Result<(), PongError> {
+        let Some(chat) = &mut self.chat else {
+            return Ok(());
+        };
+        for text in chat.take_outbox() {
+            connection.send(&ClientMessage::Chat(text))?;
+        }
+        Ok(())
+    }
+
+    pub fn set_match_rules(&mut self, match_rules: MatchRules) -> Result<(), PongError> {
+        match_rules.validate()?;
+        self.game_state.match_rules = match_rules;
+        Ok(())
+    }
+
+    pub fn get_match_result(&self) -> Option<MatchResult> {
+        self.game_state.match_result
+    }
+
+    pub fn set_serve_rules(&mut self, serve_rules: ServeRules) {
+        self.game_state.serve_rules = serve_rules;
+        // restart a pending countdown so it uses the new delay
+        if let ServeState::Countdown { toward, .. } = self.game_state.serve {
+            self.game_state.serve = ServeState::Countdown { ticks_left: serve_rules.delay_ticks, toward };
+        }
+    }
+
+    /// Plays a match on `connection`, the game must have been built for its arena.
+    pub fn run_client(&mut self, connection: &mut ServerConnection) -> Result<(), PongError> {
+        let guard = TerminalGuard::new()?;
+        let mut held = guard.report_key_releases()?.then(HeldKeys::default);
+        if self.mouse_control {
+            guard.capture_mouse()?;
+        }
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+        self.chat = Some(ChatPanel::default());
+        self.seat = Seat::Player;
+        for tick in 0u32.. {
+            let Some(message) = self.read_key_client(connection.side(), &mut held)? else {
+                // let the server end the match for the other player, it notices a
+                // dropped connection anyway if this does not get through
+                let _ = connection.send(&ClientMessage::Leave);
+                break;
+            };
+            // once a second, ahead of the input so the answer comes before the state
+            if tick.is_multiple_of(TICKS_PER_SECOND as u32) {
+                connection.ping()?;
+            }
+            self.send_chat(connection)?;
+            connection.send(&message)?;
+
+            self.receive_state(connection)?;
+            self.net_stats = Some(connection.net_stats());
+            self.draw_frame(&mut terminal)?; // draw UI
+            if let RematchVote::Declined(_) = self.rematch {
+                self.draw(&mut terminal)?;
+                self.wait_for_key(&mut terminal)?;
+                break;
+            }
+        }
+        Ok(())
     }
 
-    pub fn run_client(&mut self, stream: &mut TcpStream) -> io::Result<()> {
-        enable_raw_mode()?;
-        stdout().execute(EnterAlternateScreen)?;
+    /// Follows a match without taking part, drawing every state the server sends, and
+    /// any rematches after it.
+    pub fn run_spectator(&mut self, connection: &mut ServerConnection) -> Result<(), PongError> {
+        let _guard = TerminalGuard::new()?;
         let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+        // spectators have no paddle to move, so `q` quits as well unless they are typing
+        self.input_map.keys_mut(Binding::Quit).push(Key::new(event::KeyCode::Char('q'), event::KeyModifiers::NONE));
+        self.chat = Some(ChatPanel::default());
+        self.seat = Seat::Spectator;
         loop {
-	        let mut client_read_buffer = [0u8; 1024];
-            if !self.read_key_client(stream)? {
+            if self.read_keys(Instant::now(), &mut None)?.quit {
                 break;
             }
-            //println!("Waiting for server data");
-            match stream.read(&mut client_read_buffer) {
-                Ok(n) => {
-                    if n == 0 {
-                        process::exit(0);
-                    }
-                    else
-                    {
-                        let buffer_as_string = str::from_utf8(&client_read_buffer).unwrap().trim_matches(char::from(0));
-                        //println!("json data as string: {:?}", buffer_as_string);
-                        let json_data: GameDataJSON = serde_json::from_str(&buffer_as_string).unwrap();
-                        self.set_game_data(&json_data);
-                        //println!("json data: {:?}", json_data);
-                        //println!("{:?}", self.game_state.get_pong_pos());
-                        //io::stdout().flush().unwrap();
-                    }
-                },
-                Err(error) => {
-                    println!("{}", error.to_string());
-                    process::exit(-1);
-                },
+            self.send_chat(connection)?;
+
+            self.receive_state(connection)?;
+            self.draw_frame(&mut terminal)?;
+            if let RematchVote::Declined(_) = self.rematch {
+                self.draw(&mut terminal)?;
+                self.wait_for_key(&mut terminal)?;
+                break;
             }
-            self.draw(&mut terminal)?; // draw UI
-            self.game_state.move_pong_ball();
         }
-        disable_raw_mode()?;
-        stdout().execute(LeaveAlternateScreen)?;
         Ok(())
     }
 
-    pub fn run_server(&mut self, player_one: &mut TcpStream, player_two: &mut TcpStream) -> io::Result<()> {
+    /// Plays a match between the two connected players, and rematches for as long as
+    /// both want them. Anyone else connecting to `listener` while it runs is sent the
+    /// same states as a spectator, without a listener nobody can watch.
+    pub fn run_server(&mut self, player_one: TcpStream, player_two: TcpStream, listener: Option<&TcpListener>) -> Result<(), PongError> {
+        let mut readers = [
+            MessageReader::new(player_one.try_clone()?),
+            MessageReader::new(player_two.try_clone()?),
+        ];
+        let mut players = [player_one, player_two];
+        let mut spectators = Vec::new();
+        let (greeted_sender, greeted) = mpsc::channel();
+        let (spectator_chat_sender, spectator_chat) = mpsc::channel();
+        let mut chat_limiters = [ChatLimiter::default(), ChatLimiter::default()];
+        if let Some(listener) = listener {
+            listener.set_nonblocking(true)?;
+        }
+        let tick_length = Duration::from_secs(1) / TICKS_PER_SECOND as u32;
+        let mut next_tick = Instant::now();
         loop {
-            if event::poll(std::time::Duration::from_millis(50))? {
-                if !self.read_key()? { break }
+            // the players' messages arrive as fast as they press keys, the clock keeps
+            // the game at its normal speed
+            thread::sleep(next_tick.saturating_duration_since(Instant::now()));
+            next_tick = Instant::now() + tick_length;
+            if let Some(listener) = listener {
+                self.accept_spectators(listener, &greeted_sender, &spectator_chat_sender)?;
+            }
+            for spectator in greeted.try_iter() {
+                self.log(format_args!("Spectator {} joined from {}", spectator.name, spectator.address));
+                spectators.push(spectator);
+            }
+
+            let mut chat = Vec::new();
+            let [player_one_input, player_two_input] = self.read_key_server(&mut readers, &mut players, &mut chat);
+            if self.rematch.agreed() {
+                self.log(format_args!("Starting a rematch"));
+                self.rematch()?;
+                players.swap(0, 1);
+                readers.swap(0, 1);
+                chat_limiters.swap(0, 1);
+                for (side, player) in [Side::PlayerOne, Side::PlayerTwo].into_iter().zip(players.iter_mut()) {
+                    if let Err(error) = send_message(player, &ServerMessage::Rematch(side)) {
+                        self.log(format_args!("{:?}: {}", side, error));
+                    }
+                }
             }
+            let game_data = if self.game_state.match_result.is_none() && self.pause.advance() {
+                self.log(format_args!("Moving ball"));
+                self.step(player_one_input, player_two_input)
+            } else {
+                self.snapshot()
+            };
+            for event in &game_data.events {
+                self.log(format_args!("{:?}", event));
+            }
+            let mut messages: Vec<ServerMessage> = Vec::new();
+            if game_data.events.iter().any(|event| matches!(event, GameEvent::MatchOver(_))) {
+                // a file that cannot be written should not end the session for everyone
+                if let Err(error) = self.save_replay() {
+                    self.log(format_args!("Could not save the replay: {}", error));
+                }
+                self.career = match self.record_match() {
+                    Ok(career) => career,
+                    Err(error) => {
+                        self.log(format_args!("Could not add the match to the history: {}", error));
+                        None
+                    },
+                };
+                if let Some(career) = &self.career {
+                    messages.push(ServerMessage::PlayerStats(career.clone()));
+                }
+            }
+
+            // chat goes out ahead of the state, stamped with its tick
+            let tick = self.game_state.elapsed_ticks;
+            let mut lines = Vec::new();
+            let spectator_chat = spectator_chat.try_iter().map(|(address, text)| (ChatSender::Spectator(address), text));
+            for (sender, text) in chat.into_iter().chain(spectator_chat) {
+                let (name, side, limiter, stream) = match sender {
+                    ChatSender::Player(side) => (
+                        self.players[side as usize].name.clone(),
+                        Some(side),
+                        &mut chat_limiters[side as usize],
+                        &mut players[side as usize],
+                    ),
+                    ChatSender::Spectator(address) => {
+                        // they may have gone since
+                        let Some(spectator) = spectators.iter_mut().find(|spectator| spectator.address == address) else {
+                            continue;
+                        };
+                        (spectator.name.clone(), None, &mut spectator.chat_limiter, &mut spectator.stream)
+                    },
+                };
+                let Some(text) = chat::clean(&text) else {
+                    continue;
+                };
+                if !limiter.allow(Instant::now()) {
+                    self.log(format_args!("Dropped chat from {}: {}", name, text));
+                    let _ = send_message(stream, &ServerMessage::Chat(ChatLimiter::refusal(tick)));
+                    continue;
+                }
+                lines.push(ChatLine { tick, sender: Some(name), side, text });
+            }
+            for line in lines {
+                self.log(format_args!("Chat from {}: {}", line.sender.as_deref().unwrap_or_default(), line.text));
+                if let Some(recording) = &mut self.recording {
+                    recording.chat.push(line.clone());
+                }
+                messages.push(ServerMessage::Chat(line));
+            }
+            let message = ServerMessage::State(game_data);
+            self.log(format_args!("{:?}", message));
+            messages.push(message);
 
-            self.read_key_server(player_one, player_two);
-            println!("Moving ball");
-            self.game_state.move_pong_ball();
-            
-            let game_data = serde_json::to_string(&self.get_game_data()).unwrap();
-            println!("{:?}", game_data);
+            // a player who can no longer be reached forfeits when their next read fails
+            for (side, player) in [Side::PlayerOne, Side::PlayerTwo].into_iter().zip(players.iter_mut()) {
+                if let Err(error) = messages.iter().try_for_each(|message| send_message(player, message)) {
+                    self.log(format_args!("{:?}: {}", side, error));
+                }
+            }
+            spectators.retain_mut(|spectator| {
+                messages.iter().all(|message| send_message(&mut spectator.stream, message).is_ok())
+            });
 
-            player_one.write_all(game_data.as_bytes()).unwrap();
-            player_two.write_all(game_data.as_bytes()).unwrap();
+            // the server stays up for a rematch until one of the players leaves
+            if let RematchVote::Declined(_) = self.rematch {
+                break;
+            }
+        }
+        // ends the threads reading the spectators' chat
+        for spectator in spectators {
+            let _ = spectator.stream.shutdown(Shutdown::Read);
         }
         Ok(())
     }
 
-    pub fn run(&mut self) -> io::Result<()> {
-        enable_raw_mode()?;
-        stdout().execute(EnterAlternateScreen)?;
+    pub fn run(&mut self) -> Result<(), PongError> {
+        self.run_local(None)
+    }
+
+    /// Plays against the computer, which takes player two. The human can use either
+    /// player's keys.
+    pub fn run_vs_cpu(&mut self, difficulty: Difficulty) -> Result<(), PongError> {
+        self.run_local(Some(CpuPlayer::new(Side::PlayerTwo, difficulty)))
+    }
 
+    fn run_local(&mut self, mut cpu: Option<CpuPlayer>) -> Result<(), PongError> {
+        let guard = TerminalGuard::new()?;
+        let mut held = guard.report_key_releases()?.then(HeldKeys::default);
+        if self.mouse_control {
+            guard.capture_mouse()?;
+        }
         let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
 
+        let tick_length = Duration::from_secs(1) / TICKS_PER_SECOND as u32;
+        // the mouse plays on the left to start with, where the human is against the
+        // computer, and follows them to the other end in a rematch
+        let mut mouse_side = Side::PlayerOne;
         loop {
-            if event::poll(std::time::Duration::from_millis(50))? {
-                if !self.read_key()? { break }
+            // keys are read until the tick is due, so pressing them does not speed the
+            // game up
+            let keys = self.read_keys(Instant::now() + tick_length, &mut held)?;
+            if keys.quit {
+                // keeps the replay of what was played
+                self.save_replay()?;
+                break;
+            }
+            if keys.pause {
+                // both players share the keyboard, so one press pauses or resumes for both
+                if self.pause.is_running() {
+                    self.pause.request_pause();
+                } else {
+                    self.pause.vote_resume(Side::PlayerOne);
+                    self.pause.vote_resume(Side::PlayerTwo);
+                }
+            }
+            let mut inputs = keys.inputs;
+            if inputs[mouse_side as usize] == PaddleInput::Idle {
+                inputs[mouse_side as usize] = self.mouse_input(mouse_side)?;
+            }
+            if let Some(cpu) = &mut cpu {
+                let human = if inputs[0] == PaddleInput::Idle { inputs[1] } else { inputs[0] };
+                inputs[cpu.side().opposite() as usize] = human;
+                inputs[cpu.side() as usize] = cpu.next_input(&self.game_state);
+            }
+            self.draw_frame(&mut terminal)?; // draw UI
+            if self.pause.advance() {
+                self.step(inputs[0], inputs[1]);
+            }
+            if self.game_state.match_result.is_some() {
+                self.save_replay()?;
+                self.career = self.record_match()?;
+                self.draw(&mut terminal)?;
+                // one press at the shared keyboard answers for both players, and the
+                // computer is always up for another match
+                let key = self.wait_for_key(&mut terminal)?;
+                if self.input_map.action(&key) != Some(Action::Rematch) {
+                    break;
+                }
+                self.rematch()?;
+                self.input_map.swap_players();
+                mouse_side = mouse_side.opposite();
+                if let Some(cpu) = &mut cpu {
+                    cpu.switch_sides();
+                }
             }
-            self.draw(&mut terminal)?; // draw UI
-            self.game_state.move_pong_ball();
         }
-
-        disable_raw_mode()?;
-        stdout().execute(LeaveAlternateScreen)?;
         Ok(())
     }
 
-    fn read_key_server(&mut self, player_one: &mut TcpStream, player_two: &mut TcpStream) {
-	    let mut player_one_read_buffer: [u8; 1] = [0; 1];
-	    let mut player_two_read_buffer: [u8; 1] = [0; 1];
-        println!("Reading key on server side!");
-        match player_one.read(&mut player_one_read_buffer) {
-            Ok(n) => {
-                if n == 0 {
-                    return;
-                }
-                else {
-                    let key = str::from_utf8(&player_one_read_buffer).unwrap();
-                    if key == "w" {
-                        self.game_state.move_paddle(KeyCode::Char('w'));
-                    } else if key == "s" {
-                        self.game_state.move_paddle(KeyCode::Char('s'));
-                    }
-                    println!("{}", key);
-                }
-            },
-            Err(error) => {
-                println!("{}", error.to_string());
-                process::exit(-1);
-            },
+    // takes on anyone who connected since the last tick as a spectator, greeting each on
+    // a thread of their own. They come back through `greeted` once welcomed, and the chat
+    // they send is passed on to `chat`.
+    fn accept_spectators(
+        &self,
+        listener: &TcpListener,
+        greeted: &mpsc::Sender<Spectator>,
+        chat: &mpsc::Sender<(SocketAddr, String)>,
+    ) -> Result<(), PongError> {
+        loop {
+            match listener.accept() {
+                Ok((stream, address)) => {
+                    let (arena, logging) = (self.arena(), self.logging);
+                    let (greeted, chat) = (greeted.clone(), chat.clone());
+                    thread::spawn(move || greet_spectator(stream, address, arena, logging, greeted, chat));
+                },
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(error) => return Err(error.into()),
+            }
         }
+    }
 
-        match player_two.read(&mut player_two_read_buffer) {
-            Ok(n) => {
-                if n == 0 {
-                    return;
-                }
-                else {
-                    let key = str::from_utf8(&player_two_read_buffer).unwrap();
-                    if key == "w" {
-                        self.game_state.move_paddle(KeyCode::Up);
-                    } else if key == "s" {
-                        self.game_state.move_paddle(KeyCode::Down);
-                    }
-                    println!("{}", key);
-                }
-            },
-            Err(error) => {
-                println!("{}", error.to_string());
-                process::exit(-1);
-            },
+    // prints a line about what the server is doing, if enabled with `set_logging`
+    fn log(&self, message: fmt::Arguments) {
+        if self.logging {
+            println!("{}", message);
         }
     }
 
-    fn read_key_client(&mut self, stream: &mut TcpStream) -> io::Result<bool> {
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                match key {
-                    KeyEvent { 
-                        code: KeyCode::Char('c'), 
-                        modifiers: event::KeyModifiers::CONTROL, 
-                        ..
-                    } => return Ok(false),
-                    KeyEvent {
-                        code: KeyCode::Char('w'),
-                        modifiers: event::KeyModifiers::NONE,
-                        ..
-                    } => {
-                        let key_pressed_buffer: [u8; 1] = [b'w'];
-                        let bytes_written = stream.write(&key_pressed_buffer).unwrap();
-                        if bytes_written <= 0 {
-                            return Ok(false);
-                        } 
-                        //println!("Sent key press 'w' to server");
-                    },
-                    KeyEvent {
-                        code: KeyCode::Char('s'),
-                        modifiers: event::KeyModifiers::NONE,
-                        ..
-                    } => {
-                        let key_pressed_buffer: [u8; 1] = [b's'];
-                        let bytes_written = stream.write(&key_pressed_buffer).unwrap();
-                        if bytes_written <= 0 {
-                            return Ok(false);
-                        } 
-                        //println!("Sent key press 's' to server");
-                    },
-                    _ => {
-                        let key_pressed_buffer: [u8; 1] = [b'0'];
-                        let bytes_written = stream.write(&key_pressed_buffer).unwrap();
-                        if bytes_written <= 0 {
-                            return Ok(false);
-                        } 
-                        //println!("Sent nothing to server!");
+    // reads this tick's message from each player, applying pause requests and votes,
+    // answering pings and collecting chat into `chat` on the way. A player who leaves or
+    // whose connection drops forfeits the match and turns down a rematch.
+    fn read_key_server(
+        &mut self,
+        readers: &mut [MessageReader<TcpStream>; 2],
+        players: &mut [TcpStream; 2],
+        chat: &mut Vec<(ChatSender, String)>,
+    ) -> [PaddleInput; 2] {
+        self.log(format_args!("Reading key on server side!"));
+        let mut inputs = [PaddleInput::Idle; 2];
+        for (side, reader) in [Side::PlayerOne, Side::PlayerTwo].into_iter().zip(readers.iter_mut()) {
+            let message = loop {
+                match reader.read_message::<ClientMessage>() {
+                    Ok(ClientMessage::Ping(number)) => {
+                        if let Err(error) = send_message(&mut players[side as usize], &ServerMessage::Pong(number)) {
+                            self.log(format_args!("{:?}: {}", side, error));
+                        }
                     },
+                    Ok(ClientMessage::Chat(text)) => chat.push((ChatSender::Player(side), text)),
+                    message => break message,
                 }
-                //println!("finished reading key!");
+            };
+            match message {
+                Ok(message) => {
+                    self.log(format_args!("{:?}: {:?}", side, message));
+                    match message {
+                        ClientMessage::Input(input) => inputs[side as usize] = input,
+                        ClientMessage::PauseRequest => self.pause.request_pause(),
+                        ClientMessage::ResumeVote => self.pause.vote_resume(side),
+                        ClientMessage::RematchVote if self.game_state.match_result.is_some() => {
+                            self.rematch.accept(side);
+                        },
+                        ClientMessage::Leave => {
+                            self.forfeit(side);
+                            self.rematch.decline(side);
+                        },
+                        // already introduced when they connected, and pings and chat are
+                        // taken care of above
+                        ClientMessage::Hello { .. } | ClientMessage::Ping(_) | ClientMessage::Chat(_) => {},
+                        // there is nothing to vote on before the match ends
+                        ClientMessage::RematchVote => {},
+                    }
+                },
+                Err(error) => {
+                    self.log(format_args!("{:?}: {}", side, error));
+                    self.forfeit(side);
+                    self.rematch.decline(side);
+                },
             }
+        }
+        inputs
+    }
+
+    // the message to send the server this tick, `None` when the player quits. The player
+    // has one paddle, the one on `side`, moved by either player's keys.
+    fn read_key_client(&mut self, side: Option<Side>, held: &mut Option<HeldKeys>) -> io::Result<Option<ClientMessage>> {
+        let tick_length = Duration::from_secs(1) / TICKS_PER_SECOND as u32;
+        let keys = self.read_keys(Instant::now() + tick_length, held)?;
+        if keys.quit {
+            return Ok(None);
+        }
+        // the paddles stay put on the results screen, where only the rematch key counts
+        if self.game_state.match_result.is_some() {
+            let message = if keys.rematch { ClientMessage::RematchVote } else { ClientMessage::Input(PaddleInput::Idle) };
+            return Ok(Some(message));
+        }
+        if keys.pause {
+            let message = match self.pause {
+                PauseState::Paused { .. } => ClientMessage::ResumeVote,
+                _ => ClientMessage::PauseRequest,
+            };
+            return Ok(Some(message));
+        }
+        let [player_one, player_two] = keys.inputs;
+        let mut input = if player_one == PaddleInput::Idle { player_two } else { player_one };
+        if let (PaddleInput::Idle, Some(side)) = (input, side) {
+            input = self.mouse_input(side)?;
+        }
+        Ok(Some(ClientMessage::Input(input)))
+    }
+
+    // steers `side`'s paddle toward the row under the mouse pointer, keeping still once
+    // its middle is closer than a single move would take it
+    fn mouse_input(&self, side: Side) -> io::Result<PaddleInput> {
+        let Some(row) = self.mouse_row.filter(|_| self.mouse_control) else {
+            return Ok(PaddleInput::Idle);
+        };
+        let (columns, rows) = crossterm::terminal::size()?;
+        let Some((view, _)) = self.layout(Rect::new(0, 0, columns, rows)) else {
+            return Ok(PaddleInput::Idle);
+        };
+        let paddle = match side {
+            Side::PlayerOne => &self.game_state.player,
+            Side::PlayerTwo => &self.game_state.opponent,
+        };
+        let distance = view.logical_y(row) - (paddle.y as f32 + self.game_state.paddle_size.1 as f32 / 2.0);
+        let step = self.game_state.paddle_speed as f32 / 2.0;
+        Ok(if distance < -step {
+            PaddleInput::Up
+        } else if distance > step {
+            PaddleInput::Down
         } else {
-            //println!("No key pressed");
-            let key_pressed_buffer: [u8; 1] = [b'0'];
-            let bytes_written = stream.write(&key_pressed_buffer).unwrap();
-            if bytes_written <= 0 {
-                return Ok(false);
-            } 
-        }
-        Ok(true)
-    }
-
-    fn read_key(&mut self) -> io::Result<bool> {
-        if let Event::Key(key) = event::read()? {
-            match key {
-                KeyEvent { 
-                    code: KeyCode::Char('c'), 
-                    modifiers: event::KeyModifiers::CONTROL, 
-                    ..
-                } => return Ok(false),
-                KeyEvent {
-                    code: KeyCode::Char('w') | KeyCode::Char('s') | KeyCode::Up | KeyCode::Down,
-                    modifiers: event::KeyModifiers::NONE,
-                    ..
-                } => self.game_state.move_paddle(key.code),
+            PaddleInput::Idle
+        })
+    }
+
+    // blocks until any key is pressed so a final screen stays up, redrawing it if the
+    // terminal is resized in the meantime, and returns the key
+    fn wait_for_key<W: io::Write>(&self, terminal: &mut Terminal<CrosstermBackend<W>>) -> io::Result<KeyEvent> {
+        loop {
+            match event::read()? {
+                // the release of the last key pressed in the match is not an answer, nor
+                // is Shift or Ctrl on its own, which terminals reporting every key send
+                Event::Key(key) if key.kind != KeyEventKind::Release && !matches!(key.code, KeyCode::Modifier(_)) => {
+                    return Ok(key);
+                },
+                Event::Resize(_, _) => self.draw(terminal)?,
                 _ => {},
             }
         }
-        Ok(true)
     }
 
-    fn draw<W: io::Write>(&self, terminal: &mut Terminal<CrosstermBackend<W>>) -> io::Result<()> {
+    // collects the key presses made before `deadline`. With `held` keys, a paddle moves
+    // for as long as its key is down; without, it moves once per press the terminal
+    // reports, so at the keyboard's repeat rate.
+    fn read_keys(&mut self, deadline: Instant, held: &mut Option<HeldKeys>) -> io::Result<TickKeys> {
+        let mut keys = TickKeys::default();
+        while event::poll(deadline.saturating_duration_since(Instant::now()))? {
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                Event::Mouse(mouse) => {
+                    if matches!(mouse.kind, MouseEventKind::Moved | MouseEventKind::Drag(_) | MouseEventKind::Down(_)) {
+                        self.mouse_row = Some(mouse.row);
+                    }
+                    continue;
+                },
+                _ => continue,
+            };
+            // keys typed into the chat do not move the paddles, but letting go of one
+            // still stops its paddle
+            let typing = self.chat.as_ref().is_some_and(ChatPanel::is_typing);
+            if let Some(held) = held.as_mut().filter(|_| !typing || key.kind == KeyEventKind::Release) {
+                held.update(&key);
+            }
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            if self.chat.as_mut().is_some_and(|chat| chat.type_key(&key)) {
+                continue;
+            }
+            match self.input_map.lookup(&key) {
+                Some((Action::Quit, _)) => {
+                    keys.quit = true;
+                    return Ok(keys);
+                },
+                Some((Action::Pause, _)) => keys.pause = true,
+                Some((Action::Rematch, _)) => keys.rematch = true,
+                Some((Action::Chat, _)) => {
+                    if let Some(chat) = &mut self.chat {
+                        chat.start_typing();
+                    }
+                },
+                Some((Action::Up, Some(side))) => keys.inputs[side as usize] = PaddleInput::Up,
+                Some((Action::Down, Some(side))) => keys.inputs[side as usize] = PaddleInput::Down,
+                _ => {},
+            }
+        }
+        // a key pressed and released within the tick still moves the paddle once
+        if let Some(held) = held {
+            for (input, held_input) in keys.inputs.iter_mut().zip(held.paddle_inputs(&self.input_map)) {
+                if held_input != PaddleInput::Idle {
+                    *input = held_input;
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    // draws unless the last frame was too recent for the frame rate. Ticks do not arrive
+    // exactly on time, so a frame that is a little early still counts.
+    pub(crate) fn draw_frame<W: io::Write>(&mut self, terminal: &mut Terminal<CrosstermBackend<W>>) -> io::Result<()> {
+        let now = Instant::now();
+        let early = self.last_frame.is_some_and(|last| {
+            now.duration_since(last) + self.frame_interval / 4 < self.frame_interval
+        });
+        if early {
+            return Ok(());
+        }
+        self.last_frame = Some(now);
+        self.draw(terminal)
+    }
+
+    pub(crate) fn draw<W: io::Write>(&self, terminal: &mut Terminal<CrosstermBackend<W>>) -> io::Result<()> {
         terminal.draw(|frame| {
+            frame.render_widget(Block::new().style(self.theme.base()), frame.size());
+            let Some((view, chat_area)) = self.layout(frame.size()) else {
+                let (min_width, min_height) = ArenaView::min_size(self.game_state.dimensions);
+                let Rect { width, height, .. } = frame.size();
+                let message = Paragraph::new(vec![
+                    Line::from("Terminal too small"),
+                    Line::from(format!("Need {}x{}, have {}x{}", min_width, min_height, width, height)),
+                ])
+                    .alignment(Alignment::Center)
+                    .wrap(Wrap { trim: true });
+                frame.render_widget(message, frame.size());
+                return;
+            };
 
-            let player1Score = self.game_state.player.score;
-            let player2Score = self.game_state.opponent.score;
+            self.draw_hud(frame, &view);
 
             // draw game
-            let game_area = Block::new()
+            let mut game_area = Block::new()
                 .borders(Borders::all())
                 .border_type(BorderType::Rounded)
-                .title(
-                    format!(
-                        "RustyPong ({},{}) | P1: {} P2: {} ", 
-                        self.game_state.dimensions.width, 
-                        self.game_state.dimensions.height, 
-                        player1Score, 
-                        player2Score,
-                    )
-                )
+                .border_style(Style::new().fg(self.theme.border))
+                .title("RustyPong")
                 .title_alignment(Alignment::Center);
-            frame.render_widget(game_area, self.game_state.dimensions);
-            
-            // draw player
-            let [player_paddle_x, player_paddle_y] = self.game_state.get_player_paddle_pos();
-            let paddle_size = self.game_state.paddle_size;
-            let player_paddle= Block::new()
-                .borders(Borders::all())
-                .border_style(Style::new().light_green());
+            if let Some(footer) = &self.footer {
+                game_area = game_area.title(
+                    block::Title::from(footer.as_str()).position(block::Position::Bottom)
+                );
+            }
+            frame.render_widget(game_area, view.screen);
+
+            let (arena, area) = (self.game_state.dimensions, view.interior());
+            if !render::paint(frame, self.renderer, arena, area, &self.shapes(), self.theme.background) {
+                self.draw_cells(frame, &view);
+            }
+            if self.show_effects {
+                self.draw_effects(frame, &view);
+            }
+            self.draw_overlays(frame, &view);
+            if let (Some(chat), Some(area)) = (&self.chat, chat_area) {
+                self.draw_chat(frame, chat, area);
+            }
+        })?;
+        Ok(())
+    }
+
+    // where the arena goes in `area` and, while the chat is open, the chat panel. The
+    // panel gets columns of its own if the arena still fits beside it, otherwise it
+    // covers the right of the arena.
+    fn layout(&self, area: Rect) -> Option<(ArenaView, Option<Rect>)> {
+        let arena = self.game_state.dimensions;
+        if !self.chat.as_ref().is_some_and(ChatPanel::is_open) {
+            return ArenaView::fit(arena, area).map(|view| (view, None));
+        }
+        let beside = Rect { width: area.width.saturating_sub(CHAT_PANEL_WIDTH), ..area };
+        if let Some(view) = ArenaView::fit(arena, beside) {
+            let panel = Rect::new(view.screen.right(), view.hud.y, CHAT_PANEL_WIDTH, view.screen.bottom() - view.hud.y);
+            return Some((view, Some(panel)));
+        }
+        let view = ArenaView::fit(arena, area)?;
+        let width = CHAT_PANEL_WIDTH.min(view.screen.width / 2);
+        let panel = Rect::new(view.screen.right() - width, view.screen.y, width, view.screen.height);
+        Some((view, Some(panel)))
+    }
+
+    // the chat so far, newest at the bottom, above the message being typed
+    fn draw_chat(&self, frame: &mut Frame, chat: &ChatPanel, area: Rect) {
+        let block = Block::new()
+            .borders(Borders::all())
+            .border_type(BorderType::Rounded)
+            .border_style(Style::new().fg(self.theme.border))
+            .title("Chat")
+            .style(self.theme.base());
+        let inner = block.inner(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(block, area);
+        if inner.width == 0 || inner.height == 0 {
+            return;
+        }
+
+        let width = inner.width as usize;
+        let prompt = match chat.draft() {
+            _ if chat.is_read_only() => None,
+            Some(draft) => {
+                // the end of a long message, where the typing is
+                let typed: Vec<char> = format!("> {}_", draft).chars().collect();
+                Some(Line::from(typed[typed.len().saturating_sub(width)..].iter().collect::<String>()))
+            },
+            None => {
+                let hint = match self.key_hint(Binding::Chat) {
+                    Some(key) => format!("{} to chat", key),
+                    None => String::new(),
+                };
+                Some(Line::styled(hint, self.theme.base().add_modifier(Modifier::DIM)))
+            },
+        };
+        let rows = inner.height as usize - usize::from(prompt.is_some());
+        let mut lines: Vec<Line> = Vec::new();
+        for line in chat.lines().rev() {
+            if lines.len() >= rows {
+                break;
+            }
+            let mut wrapped = self.chat_rows(line, width);
+            wrapped.append(&mut lines);
+            lines = wrapped;
+        }
+        let lines = lines.split_off(lines.len().saturating_sub(rows));
+        let history = Rect { height: rows as u16, ..inner };
+        frame.render_widget(Paragraph::new(lines), history);
+        if let Some(prompt) = prompt {
+            frame.render_widget(Paragraph::new(prompt), Rect { y: inner.bottom() - 1, height: 1, ..inner });
+        }
+    }
+
+    // a chat line broken into rows of `width` characters, the sender's name in their
+    // player colour and notices from the server dimmed
+    fn chat_rows(&self, line: &ChatLine, width: usize) -> Vec<Line<'static>> {
+        let base = self.theme.base();
+        let (name, name_style, text_style) = match (&line.sender, line.side) {
+            (Some(name), Some(side)) => (
+                format!("{}: ", name),
+                Style::new().fg(self.player_color(side)).add_modifier(Modifier::BOLD),
+                base,
+            ),
+            (Some(name), None) => (format!("{}: ", name), base.add_modifier(Modifier::ITALIC), base),
+            (None, _) => (String::new(), base, base.add_modifier(Modifier::DIM | Modifier::ITALIC)),
+        };
+        let name_length = name.chars().count();
+        let characters: Vec<char> = name.chars().chain(line.text.chars()).collect();
+        characters
+            .chunks(width)
+            .enumerate()
+            .map(|(row, chunk)| {
+                let (name, text) = chunk.split_at(name_length.saturating_sub(row * width).min(chunk.len()));
+                Line::from(vec![
+                    Span::styled(name.iter().collect::<String>(), name_style),
+                    Span::styled(text.iter().collect::<String>(), text_style),
+                ])
+            })
+            .collect()
+    }
+
+    // names and scores on the first row with the games and the clock between them,
+    // the rally, ball speed and connection on the second
+    fn draw_hud(&self, frame: &mut Frame, view: &ArenaView) {
+        let Rect { x, y, width, .. } = view.hud;
+        let scores = Rect::new(x, y, width, 1);
+        let mut clock = format!("Games {}-{} | {}", self.game_state.player.games, self.game_state.opponent.games, hud::clock(self.game_state.elapsed_ticks));
+        if let Some(limit) = self.game_state.match_rules.time_limit_ticks() {
+            clock.push_str(&format!(" ({} left)", hud::clock(limit.saturating_sub(self.game_state.elapsed_ticks))));
+        }
+        frame.render_widget(Paragraph::new(clock).style(self.theme.base()).alignment(Alignment::Center), scores);
+
+        let player = |side: Side| Style::new().fg(self.player_color(side)).add_modifier(Modifier::BOLD);
+        let [player_one, player_two] = self.names();
+        let player_one = format!(" {}  {}", player_one, self.game_state.player.score);
+        let player_two = format!("{}  {} ", self.game_state.opponent.score, player_two);
+        frame.render_widget(Paragraph::new(player_one).style(player(Side::PlayerOne)), scores);
+        frame.render_widget(Paragraph::new(player_two).style(player(Side::PlayerTwo)).alignment(Alignment::Right), scores);
+
+        let mut stats = format!("Rally {} | Speed {:.1}", self.rally_stats.rally, self.rally_stats.speed);
+        if let Some(net_stats) = self.net_stats {
+            match net_stats.round_trip {
+                Some(round_trip) => stats.push_str(&format!(" | Ping {} ms", round_trip.as_millis())),
+                None => stats.push_str(" | Ping --"),
+            }
+            stats.push_str(&format!(" | Loss {:.0}%", net_stats.loss * 100.0));
+        }
+        let stats = Paragraph::new(stats)
+            .style(self.theme.base().add_modifier(Modifier::DIM))
+            .alignment(Alignment::Center);
+        frame.render_widget(stats, Rect::new(x, y + 1, width, 1));
+    }
+
+    // the ball's trail, the paddles and the ball, for renderers that work below the cell
+    fn shapes(&self) -> Vec<Shape> {
+        let [player_paddle_x, player_paddle_y] = self.game_state.get_player_paddle_pos();
+        let [opponent_paddle_x, opponent_paddle_y] = self.game_state.get_opponent_paddle_pos();
+        let paddle = |x: u16, y: u16, side: Side| Shape {
+            position: Vec2::new(x as f32, y as f32),
+            size: self.game_state.paddle_size,
+            color: self.paddle_color(side),
+        };
+        let mut shapes: Vec<Shape> = self.trail()
+            .map(|(position, strength)| Shape {
+                position,
+                size: self.game_state.pong_ball.size,
+                color: effects::fade(self.theme.ball, self.theme.background, strength),
+            })
+            .collect();
+        shapes.extend([
+            paddle(player_paddle_x, player_paddle_y, Side::PlayerOne),
+            paddle(opponent_paddle_x, opponent_paddle_y, Side::PlayerTwo),
+            Shape {
+                position: self.game_state.pong_ball.position,
+                size: self.game_state.pong_ball.size,
+                color: self.theme.ball,
+            },
+        ]);
+        shapes
+    }
+
+    // where the ball has just been, nothing with effects off
+    fn trail(&self) -> impl Iterator<Item = (Vec2, f32)> + '_ {
+        self.effects.trail().filter(|_| self.show_effects)
+    }
+
+    fn paddle_lit(&self, side: Side) -> bool {
+        self.show_effects && self.effects.paddle_lit(side)
+    }
+
+    fn paddle_color(&self, side: Side) -> Color {
+        if self.paddle_lit(side) { self.theme.flash } else { self.player_color(side) }
+    }
+
+    // the paddles as boxes and the ball as a letter, in whole cells
+    fn draw_cells(&self, frame: &mut Frame, view: &ArenaView) {
+        // draw the trail, fading and shrinking with age
+        for (position, strength) in self.trail() {
+            let dot = if strength < 0.5 { "·" } else { "•" };
+            let color = effects::fade(self.theme.ball, self.theme.background, strength);
             frame.render_widget(
-                player_paddle, 
-                Rect::new(
-                    player_paddle_x, 
-                    player_paddle_y,
-                    paddle_size.0, 
-                    paddle_size.1
-                )
+                Paragraph::new(dot).style(Style::new().fg(color)),
+                view.map(position, self.game_state.pong_ball.size)
             );
+        }
 
-            // draw opponent
-            let [opponent_paddle_x, opponent_paddle_y] = self.game_state.get_opponent_paddle_pos();
-            let opponent_paddle = Block::new()
-                .borders(Borders::all())
-                .border_style(Style::new().light_green());
-            frame.render_widget(
-                opponent_paddle, 
-                Rect::new(
-                    opponent_paddle_x, 
-                    opponent_paddle_y, 
-                    paddle_size.0, 
-                    paddle_size.1
-                )
+        // a paddle the ball just hit is drawn thick
+        let border_type = |side: Side| if self.paddle_lit(side) { BorderType::Thick } else { BorderType::Plain };
+
+        // draw player
+        let [player_paddle_x, player_paddle_y] = self.game_state.get_player_paddle_pos();
+        let paddle_size = self.game_state.paddle_size;
+        let player_paddle= Block::new()
+            .borders(Borders::all())
+            .border_type(border_type(Side::PlayerOne))
+            .border_style(Style::new().fg(self.paddle_color(Side::PlayerOne)));
+        frame.render_widget(
+            player_paddle, 
+            view.map(Vec2::new(player_paddle_x as f32, player_paddle_y as f32), paddle_size)
+        );
+
+        // draw opponent
+        let [opponent_paddle_x, opponent_paddle_y] = self.game_state.get_opponent_paddle_pos();
+        let opponent_paddle = Block::new()
+            .borders(Borders::all())
+            .border_type(border_type(Side::PlayerTwo))
+            .border_style(Style::new().fg(self.paddle_color(Side::PlayerTwo)));
+        frame.render_widget(
+            opponent_paddle, 
+            view.map(Vec2::new(opponent_paddle_x as f32, opponent_paddle_y as f32), paddle_size)
+        );
+
+        // draw pong ball
+        let pong_ball = Paragraph::new("o").style(Style::new().fg(self.theme.ball));
+        frame.render_widget(
+            pong_ball, 
+            view.map(self.game_state.pong_ball.position, self.game_state.pong_ball.size)
+        );
+    }
+
+    // wall bounces and the score animation
+    fn draw_effects(&self, frame: &mut Frame, view: &ArenaView) {
+        // light up the border a couple of cells either side of a bounce
+        let interior = view.interior();
+        for flash in self.effects.wall_flashes() {
+            let row = match flash.wall {
+                Wall::Top => view.screen.y,
+                Wall::Bottom => view.screen.bottom() - 1,
+            };
+            let column = view.map(Vec2::new(flash.x as f32, self.game_state.dimensions.y as f32 + 1.0), (1, 1)).x;
+            let left = column.saturating_sub(2).max(interior.x);
+            let right = (column + 3).min(interior.right());
+            frame.buffer_mut().set_style(
+                Rect::new(left, row, right - left, 1),
+                Style::new().fg(self.theme.flash).add_modifier(Modifier::BOLD),
             );
+        }
+
+        // the news rises up the scorer's half with sparkles either side, a game won
+        // takes the whole width
+        if let Some(score) = self.effects.score() {
+            let Rect { x, y, width, height } = view.screen;
+            let sparkle = ["·", "+", "*", "+"][(score.age / 2) as usize % 4];
+            let player = self.names()[score.scorer as usize];
+            let (text, left, columns) = if score.game_won {
+                (format!("{0} {1} takes the game! {0}", sparkle, player), x + 1, width - 2)
+            } else {
+                let half = (width - 2) / 2;
+                let left = match score.scorer {
+                    Side::PlayerOne => x + 1,
+                    Side::PlayerTwo => x + 1 + half,
+                };
+                (format!("{0} {1} scores {0}", sparkle, player), left, half)
+            };
+            let row = y + height * 3 / 4 - (score.age / 4).min(height / 8);
+            let news = Paragraph::new(text)
+                .style(Style::new().fg(self.player_color(score.scorer)).add_modifier(Modifier::BOLD))
+                .alignment(Alignment::Center);
+            frame.render_widget(news, Rect::new(left, row, columns, 1));
+        }
+    }
+
+    // the lines under the result saying where the vote on a rematch stands and which
+    // keys this terminal can press about it
+    fn rematch_lines(&self, result: &MatchResult) -> Vec<String> {
+        let names = self.names();
+        let accepted = match self.rematch {
+            RematchVote::Open { accepted } => accepted,
+            RematchVote::Declined(side) => {
+                let mut lines = Vec::new();
+                // a forfeit says so already
+                if result.reason != MatchEnd::Forfeit(side) {
+                    lines.push(format!("{} left", names[side as usize]));
+                }
+                lines.push(String::from("Press any key to exit"));
+                return lines;
+            },
+        };
+        let rematch_key = self.key_hint(Binding::Rematch);
+        let vote = |ready: bool| if ready { "ready" } else { "waiting" };
+        let votes = format!("Rematch? {} {} | {} {}", names[0], vote(accepted[0]), names[1], vote(accepted[1]));
+        match (self.seat, rematch_key) {
+            (Seat::Local, Some(key)) => vec![format!("Press {} for a rematch", key), String::from("or any other key to exit")],
+            (Seat::Local, None) => vec![String::from("Press any key to exit")],
+            (Seat::Player, key) => {
+                let leave = self.key_hint(Binding::Quit).map(|quit| format!("{} to leave", quit));
+                let keys = match (key, leave) {
+                    (Some(key), Some(leave)) => format!("{} for a rematch, {}", key, leave),
+                    (Some(key), None) => format!("{} for a rematch", key),
+                    (None, leave) => leave.unwrap_or_default(),
+                };
+                vec![votes, keys]
+            },
+            (Seat::Spectator, _) => vec![votes],
+        }
+    }
+
+    // name of the first key bound to `binding`, for hints
+    fn key_hint(&self, binding: Binding) -> Option<String> {
+        self.input_map.keys(binding).first().map(Key::to_string)
+    }
 
-            // draw pong ball
-            let pong_ball = Paragraph::new("o");
+    // serve countdown, pause status and the final result, over the arena
+    fn draw_overlays(&self, frame: &mut Frame, view: &ArenaView) {
+        // draw serve countdown above the waiting ball
+        if let Some(seconds_left) = self.game_state.serve.seconds_left() {
+            let Rect { x, y, width, height } = view.screen;
+            let countdown = Paragraph::new(format!("Serve in {}", seconds_left))
+                .alignment(Alignment::Center);
             frame.render_widget(
-                pong_ball, 
-                self.game_state.pong_ball.dimensions
+                countdown,
+                Rect::new(x + 1, y + height / 2 - height / 4, width - 2, 1)
             );
-        })?;
-        Ok(())
+        }
+
+        // draw pause status over the arena
+        let pause_lines = match self.pause {
+            PauseState::Running => None,
+            PauseState::Paused { resume_votes } => {
+                let names = self.names();
+                let vote = |ready: bool| if ready { "ready" } else { "waiting" };
+                let mut lines = vec![
+                    Line::from("Paused"),
+                    Line::from(format!("{} {} | {} {}", names[0], vote(resume_votes[0]), names[1], vote(resume_votes[1]))),
+                ];
+                // spectators have no say in when the match resumes
+                if let (Seat::Local | Seat::Player, Some(key)) = (self.seat, self.key_hint(Binding::Pause)) {
+                    lines.push(Line::from(format!("Press {} to resume", key)));
+                }
+                Some(lines)
+            },
+            PauseState::Resuming { ticks_left } => Some(vec![
+                Line::from(format!("Resuming in {}", ticks_left.div_ceil(TICKS_PER_SECOND))),
+            ]),
+        };
+        if let Some(lines) = pause_lines {
+            let widest = lines.iter().map(Line::width).max().unwrap_or_default() as u16;
+            let area = centered_rect(view.screen, widest.saturating_add(4).max(30), lines.len() as u16 + 2);
+            let pause_box = Paragraph::new(lines)
+                .style(self.theme.base())
+                .alignment(Alignment::Center)
+                .block(Block::new().borders(Borders::all()));
+            frame.render_widget(Clear, area);
+            frame.render_widget(pause_box, area);
+        }
+
+        // draw final result over the arena
+        if let Some(result) = self.game_state.match_result {
+            let names = self.names();
+            let mut lines = vec![
+                Line::from(result.headline(names)),
+                Line::from(format!("Games {} - {}", result.player_one_games, result.player_two_games)),
+                Line::from(format!("Last game {} - {}", result.player_one_score, result.player_two_score)),
+            ];
+            if result.reason == MatchEnd::TimeLimit {
+                lines.push(Line::from("Time limit reached"));
+            }
+            if let Some(detail) = result.detail(names) {
+                lines.push(Line::from(detail));
+            }
+            let MatchStats { hits, longest_rally, .. } = result.stats;
+            lines.extend([
+                Line::from(""),
+                Line::from(format!("Duration {}", hud::clock(result.elapsed_ticks))),
+                Line::from(format!("Longest rally {}", longest_rally)),
+                Line::from(format!("Hits {} {} - {} {}", names[0], hits[0], hits[1], names[1])),
+            ]);
+            if let Some(career) = &self.career {
+                lines.push(Line::from(""));
+                lines.push(Line::from("All matches"));
+                for stats in career {
+                    lines.push(Line::from(format!("{} rated {} ({:+})", stats.name, stats.rating, stats.rating_change)));
+                    lines.push(Line::from(format!(
+                        "{}W {}L, points {}-{}, rally {}",
+                        stats.wins, stats.losses, stats.points_for, stats.points_against, stats.longest_rally,
+                    )));
+                }
+            }
+            // replays show their own controls along the bottom
+            if self.footer.is_none() {
+                lines.push(Line::from(""));
+                lines.extend(self.rematch_lines(&result).into_iter().map(Line::from));
+            }
+
+            let area = centered_rect(view.screen, 48, lines.len() as u16 + 2);
+            let results = Paragraph::new(lines)
+                .style(self.theme.base())
+                .alignment(Alignment::Center)
+                .block(Block::new().borders(Borders::all()).title("Match Over"));
+            frame.render_widget(Clear, area);
+            frame.render_widget(results, area);
+        }
+    }
+}
+
+// a `width` x `height` rect centred in `area`, shrunk to fit if needed
+fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+// welcomes someone who connected mid-match as a spectator and hands them to `greeted`,
+// then forwards their chat. It runs on a thread of its own, so a connection that never
+// says hello cannot hold up the match.
+fn greet_spectator(
+    mut stream: TcpStream,
+    address: SocketAddr,
+    arena: ArenaConfig,
+    logging: bool,
+    greeted: mpsc::Sender<Spectator>,
+    chat: mpsc::Sender<(SocketAddr, String)>,
+) {
+    let (spectator, reader) = match welcome_spectator(&mut stream, arena) {
+        Ok(welcomed) => welcomed,
+        Err(error) => {
+            if logging {
+                println!("Turned away spectator from {}: {}", address, error);
+            }
+            return;
+        },
+    };
+    let spectator = Spectator { stream, address, name: spectator.name, chat_limiter: ChatLimiter::default() };
+    if greeted.send(spectator).is_ok() {
+        forward_spectator_chat(reader, address, chat);
+    }
+}
+
+// the handshake for a spectator, returning who they are and a reader for their chat
+fn welcome_spectator(stream: &mut TcpStream, arena: ArenaConfig) -> Result<(PlayerInfo, MessageReader<TcpStream>), PongError> {
+    stream.set_nonblocking(false)?;
+    let spectator = welcome_client(stream, arena, None)?;
+    // a stalled spectator is dropped rather than holding up the players
+    stream.set_write_timeout(Some(SPECTATOR_WRITE_TIMEOUT))?;
+    Ok((spectator, MessageReader::new(stream.try_clone()?)))
+}
+
+// passes on the chat a spectator sends until their connection closes or the match ends
+fn forward_spectator_chat(mut reader: MessageReader<TcpStream>, address: SocketAddr, chat: mpsc::Sender<(SocketAddr, String)>) {
+    while let Ok(message) = reader.read_message::<ClientMessage>() {
+        if let ClientMessage::Chat(text) = message {
+            if chat.send((address, text)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use crate::serve::ServeDirection;
+
+    fn arb_input() -> impl Strategy<Value = PaddleInput> {
+        prop_oneof![
+            Just(PaddleInput::Idle),
+            Just(PaddleInput::Up),
+            Just(PaddleInput::Down),
+        ]
+    }
+
+    // a match with short serve delays and random serve angles, so the seed matters
+    fn seeded_game(seed: u64) -> TerminalOutput {
+        let mut term = TerminalOutput::new(ArenaConfig::default(), seed).unwrap();
+        term.set_serve_rules(ServeRules { delay_ticks: 2, randomize_angle: true, ..ServeRules::default() });
+        term.set_match_rules(MatchRules { points_per_game: 1000, ..MatchRules::default() }).unwrap();
+        term
+    }
+
+    proptest! {
+        #[test]
+        fn arena_validation_never_panics(
+            x in any::<u16>(),
+            y in any::<u16>(),
+            width in any::<u16>(),
+            height in any::<u16>(),
+            paddle_size in (0u16..20, 0u16..20),
+        ) {
+            let dimensions = Rect { x, y, width, height };
+            if let Ok(game_state) = GameState::new(dimensions, paddle_size, 0) {
+                prop_assert!(game_state.is_within_arena());
+            }
+        }
+
+        #[test]
+        fn ball_and_paddles_stay_in_arena(
+            width in 9u16..200,
+            height in 5u16..100,
+            paddle_size in (1u16..6, 1u16..12),
+            velocity in (-12.0f32..12.0, -12.0f32..12.0),
+            inputs in prop::collection::vec((arb_input(), arb_input()), 1..400),
+        ) {
+            let dimensions = Rect::new(0, 0, width, height);
+            prop_assume!(validate_arena(dimensions, paddle_size).is_ok());
+
+            let mut game_state = GameState::new(dimensions, paddle_size, 0).unwrap();
+            game_state.serve = ServeState::InPlay;
+            game_state.pong_ball.velocity = Vec2::new(velocity.0, velocity.1);
+
+            for (player_one, player_two) in inputs {
+                game_state.tick(player_one, player_two);
+                prop_assert!(game_state.is_within_arena());
+            }
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn same_seed_and_inputs_give_identical_snapshots(
+            seed in any::<u64>(),
+            inputs in prop::collection::vec((arb_input(), arb_input()), 2000..5000),
+        ) {
+            let mut first = seeded_game(seed);
+            let mut second = seeded_game(seed);
+
+            for (tick, &(player_one, player_two)) in inputs.iter().enumerate() {
+                let first_snapshot = serde_json::to_string(&first.step(player_one, player_two)).unwrap();
+                let second_snapshot = serde_json::to_string(&second.step(player_one, player_two)).unwrap();
+                prop_assert_eq!(first_snapshot, second_snapshot, "diverged on tick {}", tick);
+            }
+        }
+    }
+
+    // an 80 by 40 arena with the ball in play at `position`, moving at `velocity`
+    fn ball_in_play(position: Vec2, velocity: Vec2) -> GameState {
+        let mut game_state = GameState::new(Rect::new(0, 0, 80, 40), (2, 4), 0).unwrap();
+        game_state.serve = ServeState::InPlay;
+        game_state.pong_ball.position = position;
+        game_state.pong_ball.velocity = velocity;
+        game_state
+    }
+
+    #[test]
+    fn a_ball_faster_than_the_paddle_is_wide_still_bounces() {
+        // the paddle covers columns 1 to 2, the ball would jump from 10 straight past it
+        let [paddle_x, paddle_y] = ball_in_play(Vec2::ZERO, Vec2::ZERO).get_player_paddle_pos();
+        assert_eq!(paddle_x, 1);
+        let mut game_state = ball_in_play(Vec2::new(10.0, paddle_y as f32 + 1.0), Vec2::new(-12.0, 0.0));
+
+        game_state.tick(PaddleInput::Idle, PaddleInput::Idle);
+
+        assert_eq!(game_state.take_events(), vec![GameEvent::PaddleHit { side: Side::PlayerOne }]);
+        assert_eq!(game_state.pong_ball.velocity, Vec2::new(12.0, 0.0));
+        assert_eq!(game_state.pong_ball.position, Vec2::new(8.0, paddle_y as f32 + 1.0));
+    }
+
+    #[test]
+    fn a_paddle_moving_into_the_ball_pushes_it_out_in_front() {
+        // the ball rests just above the paddle, which moves up into it
+        let [_, paddle_y] = ball_in_play(Vec2::ZERO, Vec2::ZERO).get_player_paddle_pos();
+        let mut game_state = ball_in_play(Vec2::new(2.0, paddle_y as f32 - 1.0), Vec2::ZERO);
+
+        game_state.tick(PaddleInput::Up, PaddleInput::Idle);
+
+        assert_eq!(game_state.get_player_paddle_pos()[1], paddle_y - 1);
+        assert_eq!(game_state.take_events(), vec![GameEvent::PaddleHit { side: Side::PlayerOne }]);
+        assert_eq!(game_state.pong_ball.position, Vec2::new(3.0, paddle_y as f32 - 1.0));
+        assert!(game_state.is_within_arena());
+    }
+
+    // an 80 by 40 arena played under `rules`
+    fn game_with(rules: MatchRules) -> GameState {
+        let mut game_state = GameState::new(Rect::new(0, 0, 80, 40), (2, 4), 0).unwrap();
+        game_state.match_rules = rules;
+        game_state
+    }
+
+    #[test]
+    fn games_are_counted_until_one_player_has_most_of_them() {
+        let mut game_state = game_with(MatchRules { points_per_game: 3, win_by: 2, games: 3, time_limit_secs: None });
+
+        // 3-2 is not enough, 4-2 takes the game and starts the next from nothing
+        for scorer in [Side::PlayerOne, Side::PlayerOne, Side::PlayerTwo, Side::PlayerTwo, Side::PlayerOne] {
+            game_state.award_point(scorer);
+        }
+        assert_eq!((game_state.player.games, game_state.opponent.games), (0, 0));
+        game_state.award_point(Side::PlayerOne);
+        assert_eq!((game_state.player.games, game_state.opponent.games), (1, 0));
+        assert_eq!((game_state.player.score, game_state.opponent.score), (0, 0));
+        assert_eq!(game_state.match_result, None);
+
+        for _ in 0..3 {
+            game_state.award_point(Side::PlayerTwo);
+        }
+        assert_eq!(game_state.match_result, None);
+        for _ in 0..3 {
+            game_state.award_point(Side::PlayerOne);
+        }
+        let result = game_state.match_result.unwrap();
+        assert_eq!((result.winner, result.reason), (Some(Side::PlayerOne), MatchEnd::GamesWon));
+        assert_eq!((result.player_one_games, result.player_two_games), (2, 1));
+        assert_eq!((result.player_one_score, result.player_two_score), (3, 0));
+        assert_eq!(game_state.take_events().last(), Some(&GameEvent::MatchOver(result)));
+    }
+
+    #[test]
+    fn the_time_limit_ends_the_match_with_whoever_is_ahead() {
+        let rules = MatchRules { time_limit_secs: Some(1), ..MatchRules::default() };
+        let limit = rules.time_limit_ticks().unwrap();
+
+        let mut level = game_with(rules);
+        for _ in 1..limit {
+            level.tick(PaddleInput::Idle, PaddleInput::Idle);
+        }
+        assert_eq!(level.match_result, None);
+        level.tick(PaddleInput::Idle, PaddleInput::Idle);
+        let result = level.match_result.unwrap();
+        assert_eq!((result.winner, result.reason, result.elapsed_ticks), (None, MatchEnd::TimeLimit, limit));
+
+        let mut ahead = game_with(rules);
+        ahead.award_point(Side::PlayerTwo);
+        for _ in 0..limit {
+            ahead.tick(PaddleInput::Idle, PaddleInput::Idle);
+        }
+        assert_eq!(ahead.match_result.unwrap().winner, Some(Side::PlayerTwo));
+    }
+
+    #[test]
+    fn the_ball_is_served_once_the_delay_runs_out() {
+        let mut game_state = game_with(MatchRules::default());
+        let delay = game_state.serve_rules.delay_ticks;
+        assert_eq!(game_state.serve, ServeState::Countdown { ticks_left: delay, toward: Side::PlayerTwo });
+
+        for _ in 1..delay {
+            game_state.tick(PaddleInput::Idle, PaddleInput::Idle);
+        }
+        assert_eq!(game_state.serve, ServeState::Countdown { ticks_left: 1, toward: Side::PlayerTwo });
+        assert_eq!(game_state.pong_ball.velocity, Vec2::ZERO);
+        game_state.tick(PaddleInput::Idle, PaddleInput::Idle);
+        assert_eq!(game_state.serve, ServeState::InPlay);
+        assert!(game_state.pong_ball.velocity.x > 0.0);
+    }
+
+    #[test]
+    fn the_serve_goes_to_the_conceder_or_alternates() {
+        // player two receives the opening serve, then player one wins the next two points
+        let receivers = |direction| {
+            let mut game_state = game_with(MatchRules::default());
+            game_state.serve_rules.direction = direction;
+            let mut receivers = Vec::new();
+            for _ in 0..2 {
+                while game_state.serve != ServeState::InPlay {
+                    game_state.tick(PaddleInput::Idle, PaddleInput::Idle);
+                }
+                game_state.award_point(Side::PlayerOne);
+                match game_state.serve {
+                    ServeState::Countdown { toward, .. } => receivers.push(toward),
+                    ServeState::InPlay => panic!("a point should start a new serve"),
+                }
+            }
+            receivers
+        };
+
+        assert_eq!(receivers(ServeDirection::TowardConceder), [Side::PlayerTwo, Side::PlayerTwo]);
+        assert_eq!(receivers(ServeDirection::Alternate), [Side::PlayerOne, Side::PlayerTwo]);
     }
-}
\ No newline at end of file
+}
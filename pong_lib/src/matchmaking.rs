@@ -0,0 +1,126 @@
+use std::time::{Duration, Instant};
+
+/// Furthest apart two ratings can be to play each other as soon as both are queued.
+const INITIAL_WINDOW: i32 = 100;
+
+/// How much further apart the ratings may be for every `WINDOW_STEP` a player waits.
+const WINDOW_GROWTH: i32 = 50;
+
+const WINDOW_STEP: Duration = Duration::from_secs(10);
+
+/// Furthest apart two ratings can be to play each other once someone has waited
+/// `waited`. The window keeps widening, so nobody waits forever for a close match.
+pub fn rating_window(waited: Duration) -> i32 {
+    let steps = (waited.as_millis() / WINDOW_STEP.as_millis()).min(i32::MAX as u128) as i32;
+    INITIAL_WINDOW.saturating_add(WINDOW_GROWTH.saturating_mul(steps))
+}
+
+/// Players waiting for a ranked match, each carrying whatever the server needs to start
+/// it, e.g. their connection.
+#[derive(Debug)]
+pub struct RankedQueue<T> {
+    /// Oldest first.
+    waiting: Vec<Waiting<T>>,
+}
+
+#[derive(Debug)]
+struct Waiting<T> {
+    player: T,
+    rating: i32,
+    since: Instant,
+}
+
+impl<T> Default for RankedQueue<T> {
+    fn default() -> Self {
+        Self { waiting: Vec::new() }
+    }
+}
+
+impl<T> RankedQueue<T> {
+    pub fn join(&mut self, player: T, rating: i32, now: Instant) {
+        self.waiting.push(Waiting { player, rating, since: now });
+    }
+
+    pub fn len(&self) -> usize {
+        self.waiting.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiting.is_empty()
+    }
+
+    /// Whether anyone waiting matches `found`, e.g. has the same name as a newcomer.
+    pub fn contains(&self, mut found: impl FnMut(&T) -> bool) -> bool {
+        self.waiting.iter().any(|waiting| found(&waiting.player))
+    }
+
+    /// Drops everyone `keep` turns down, e.g. players who have disconnected.
+    pub fn retain(&mut self, mut keep: impl FnMut(&mut T) -> bool) {
+        self.waiting.retain_mut(|waiting| keep(&mut waiting.player));
+    }
+
+    /// Takes the next two players to face each other, the one who has waited longer
+    /// first: the longest waiting player with anyone inside their rating window, and
+    /// whoever of those is rated closest to them.
+    pub fn pair(&mut self, now: Instant) -> Option<(T, T)> {
+        let (first, second) = self.waiting.iter().enumerate().find_map(|(first, waiting)| {
+            let window = rating_window(now.saturating_duration_since(waiting.since));
+            self.waiting[first + 1..]
+                .iter()
+                .enumerate()
+                .map(|(offset, other)| (first + 1 + offset, (waiting.rating - other.rating).abs()))
+                .filter(|(_, gap)| *gap <= window)
+                .min_by_key(|(_, gap)| *gap)
+                .map(|(second, _)| (first, second))
+        })?;
+        // the later one first, so the earlier index is still right
+        let second = self.waiting.remove(second);
+        let first = self.waiting.remove(first);
+        Some((first.player, second.player))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_window_widens_with_every_step_waited() {
+        assert_eq!(rating_window(Duration::ZERO), INITIAL_WINDOW);
+        assert_eq!(rating_window(WINDOW_STEP - Duration::from_millis(1)), INITIAL_WINDOW);
+        assert_eq!(rating_window(WINDOW_STEP), INITIAL_WINDOW + WINDOW_GROWTH);
+        assert_eq!(rating_window(WINDOW_STEP * 5), INITIAL_WINDOW + 5 * WINDOW_GROWTH);
+        assert_eq!(rating_window(Duration::MAX), i32::MAX);
+    }
+
+    #[test]
+    fn the_longest_waiting_player_goes_first() {
+        let start = Instant::now();
+        let mut queue = RankedQueue::default();
+        queue.join("first", 1500, start);
+        queue.join("second", 1550, start + Duration::from_secs(1));
+        queue.join("third", 1510, start + Duration::from_secs(2));
+
+        // the closest rated of those in the window, with the one who waited longer first
+        assert_eq!(queue.pair(start + Duration::from_secs(3)), Some(("first", "third")));
+        assert_eq!(queue.pair(start + Duration::from_secs(3)), None);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn players_outside_the_window_wait_until_it_widens() {
+        let start = Instant::now();
+        let mut queue = RankedQueue::default();
+        queue.join("strong", 1800, start);
+        queue.join("weak", 1500, start);
+        queue.join("close", 1480, start);
+
+        // the pair in each other's window play while the other keeps waiting
+        assert_eq!(queue.pair(start), Some(("weak", "close")));
+        queue.join("newcomer", 1500, start + WINDOW_STEP);
+        assert_eq!(queue.pair(start + WINDOW_STEP), None);
+        // after 40 seconds the strong player's window reaches 300 points
+        assert_eq!(queue.pair(start + WINDOW_STEP * 4), Some(("strong", "newcomer")));
+        assert!(queue.is_empty());
+    }
+}
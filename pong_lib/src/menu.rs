@@ -0,0 +1,867 @@
+use std::io::{self, stdout};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::{panic, thread};
+use std::time::{Duration, Instant};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::prelude::{CrosstermBackend, Terminal, *};
+use ratatui::widgets::*;
+use crate::ai::Difficulty;
+use crate::config::ArenaConfig;
+use crate::error::PongError;
+use crate::history::{fetch_player_stats, PlayerStats};
+use crate::input::{Binding, Key};
+use crate::protocol::{welcome_client, PlayerInfo, ServerConnection, DEFAULT_PORT};
+use crate::render::Renderer;
+use crate::replay::{list_replays, Replay, ReplayPlayer};
+use crate::rng::seed_from_time;
+use crate::settings::{Settings, FRAME_RATES, MAX_NAME_LEN, PLAYER_COLORS};
+use crate::terminal::TerminalGuard;
+use crate::theme::Theme;
+use crate::{centered_rect, Side, TerminalOutput};
+
+/// Entries of the main menu, top to bottom.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MenuItem {
+    PlayLocal,
+    PlayCpu,
+    Host,
+    Join,
+    Replays,
+    Leaderboard,
+    Settings,
+    Quit,
+}
+
+const MENU_ITEMS: [MenuItem; 8] = [
+    MenuItem::PlayLocal,
+    MenuItem::PlayCpu,
+    MenuItem::Host,
+    MenuItem::Join,
+    MenuItem::Replays,
+    MenuItem::Leaderboard,
+    MenuItem::Settings,
+    MenuItem::Quit,
+];
+
+impl MenuItem {
+    fn label(&self) -> &'static str {
+        match self {
+            MenuItem::PlayLocal => "Play Local",
+            MenuItem::PlayCpu => "Play vs CPU",
+            MenuItem::Host => "Host",
+            MenuItem::Join => "Join",
+            MenuItem::Replays => "Replays",
+            MenuItem::Leaderboard => "Leaderboard",
+            MenuItem::Settings => "Settings",
+            MenuItem::Quit => "Quit",
+        }
+    }
+}
+
+/// Rows of the settings screen, top to bottom.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SettingsField {
+    PlayerName,
+    PlayerColor,
+    ServerAddress,
+    ApiPort,
+    Difficulty,
+    FrameRate,
+    Renderer,
+    Theme,
+    Effects,
+    MouseControl,
+    Key(Binding),
+}
+
+const SETTINGS_FIELDS: [SettingsField; 18] = [
+    SettingsField::PlayerName,
+    SettingsField::PlayerColor,
+    SettingsField::ServerAddress,
+    SettingsField::ApiPort,
+    SettingsField::Difficulty,
+    SettingsField::FrameRate,
+    SettingsField::Renderer,
+    SettingsField::Theme,
+    SettingsField::Effects,
+    SettingsField::MouseControl,
+    SettingsField::Key(Binding::PlayerOneUp),
+    SettingsField::Key(Binding::PlayerOneDown),
+    SettingsField::Key(Binding::PlayerTwoUp),
+    SettingsField::Key(Binding::PlayerTwoDown),
+    SettingsField::Key(Binding::Pause),
+    SettingsField::Key(Binding::Quit),
+    SettingsField::Key(Binding::Chat),
+    SettingsField::Key(Binding::Rematch),
+];
+
+impl SettingsField {
+    fn label(&self) -> &'static str {
+        match self {
+            SettingsField::PlayerName => "Player name",
+            SettingsField::PlayerColor => "Player colour",
+            SettingsField::ServerAddress => "Server address",
+            SettingsField::ApiPort => "Leaderboard port",
+            SettingsField::Difficulty => "CPU difficulty",
+            SettingsField::FrameRate => "Frame rate",
+            SettingsField::Renderer => "Graphics",
+            SettingsField::Theme => "Theme",
+            SettingsField::Effects => "Effects",
+            SettingsField::MouseControl => "Mouse control",
+            SettingsField::Key(binding) => binding.label(),
+        }
+    }
+
+    fn value(&self, settings: &Settings) -> String {
+        match self {
+            SettingsField::PlayerName => settings.player_name.clone(),
+            SettingsField::PlayerColor => match settings.player_color {
+                Some(color) => color.to_string(),
+                None => String::from("From theme"),
+            },
+            SettingsField::ServerAddress => settings.server_address.clone(),
+            SettingsField::ApiPort => settings.api_port.to_string(),
+            SettingsField::Difficulty => String::from(settings.difficulty.label()),
+            SettingsField::FrameRate => format!("{} fps", settings.frame_rate),
+            SettingsField::Renderer => String::from(settings.renderer.label()),
+            SettingsField::Theme => settings.theme.clone(),
+            SettingsField::Effects => String::from(if settings.effects { "On" } else { "Off" }),
+            SettingsField::MouseControl => String::from(if settings.mouse_control { "On" } else { "Off" }),
+            SettingsField::Key(binding) => {
+                let keys: Vec<String> = settings.keys.keys(*binding).iter().map(Key::to_string).collect();
+                keys.join(", ")
+            },
+        }
+    }
+
+    // typed in rather than picked from a list
+    fn is_text(&self) -> bool {
+        matches!(self, SettingsField::PlayerName | SettingsField::ServerAddress | SettingsField::ApiPort)
+    }
+
+    // the text being edited and its longest allowed length, for text fields other than
+    // the port, which is typed in digit by digit
+    fn text<'a>(&self, settings: &'a mut Settings) -> Option<(&'a mut String, usize)> {
+        match self {
+            SettingsField::PlayerName => Some((&mut settings.player_name, MAX_NAME_LEN)),
+            SettingsField::ServerAddress => Some((&mut settings.server_address, usize::MAX)),
+            SettingsField::ApiPort
+            | SettingsField::PlayerColor
+            | SettingsField::Difficulty
+            | SettingsField::FrameRate
+            | SettingsField::Renderer
+            | SettingsField::Theme
+            | SettingsField::Effects
+            | SettingsField::MouseControl
+            | SettingsField::Key(_) => None,
+        }
+    }
+
+    // steps a list field to the next or, with `forward` false, the previous choice. Themes
+    // are picked from `themes`.
+    fn cycle(&self, settings: &mut Settings, themes: &[String], forward: bool) {
+        fn step<T: Clone + PartialEq>(choices: &[T], current: &T, forward: bool) -> T {
+            let index = choices.iter().position(|choice| choice == current).unwrap_or(0);
+            let next = if forward { index + 1 } else { index + choices.len() - 1 };
+            choices[next % choices.len()].clone()
+        }
+        match self {
+            SettingsField::PlayerColor => settings.player_color = step(&PLAYER_COLORS, &settings.player_color, forward),
+            SettingsField::Difficulty => settings.difficulty = step(&Difficulty::ALL, &settings.difficulty, forward),
+            SettingsField::FrameRate => settings.frame_rate = step(&FRAME_RATES, &settings.frame_rate, forward),
+            SettingsField::Renderer => settings.renderer = step(&Renderer::ALL, &settings.renderer, forward),
+            SettingsField::Theme if !themes.is_empty() => settings.theme = step(themes, &settings.theme, forward),
+            SettingsField::Effects => settings.effects = !settings.effects,
+            SettingsField::MouseControl => settings.mouse_control = !settings.mouse_control,
+            SettingsField::Theme
+            | SettingsField::PlayerName
+            | SettingsField::ServerAddress
+            | SettingsField::ApiPort
+            | SettingsField::Key(_) => {},
+        }
+    }
+}
+
+// what the menu is showing
+enum Screen {
+    Main,
+    /// Typing the address of the server to join, or whose leaderboard to show.
+    Address(AddressFor),
+    /// Picking a recorded match to watch.
+    Replays { files: Vec<PathBuf>, selected: usize },
+    /// A server's players, highest rated first.
+    Leaderboard { players: Vec<PlayerStats>, selected: usize },
+    /// Changing preferences, `editing` while typing into a text field or waiting for a
+    /// key to bind.
+    Settings { selected: usize, editing: bool },
+}
+
+// what the server address being typed is for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AddressFor {
+    Join,
+    Leaderboard,
+}
+
+// what the user picked, carried out once the menu has handed back the terminal
+enum Action {
+    PlayLocal,
+    PlayCpu,
+    Host,
+    Join(String),
+    WatchReplay(PathBuf),
+    Quit,
+}
+
+/// The client's main menu. Each choice runs to completion and returns here, with a line
+/// at the bottom saying how it went.
+pub struct MainMenu {
+    screen: Screen,
+    selected: usize,
+    settings: Settings,
+    /// The settings' theme, kept loaded to draw the menu with.
+    theme: Theme,
+    /// Names of the themes to choose from in the settings.
+    themes: Vec<String>,
+    address: String,
+    status: Option<String>,
+}
+
+impl MainMenu {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            screen: Screen::Main,
+            selected: 0,
+            address: settings.server_address.clone(),
+            theme: settings.theme(),
+            themes: Vec::new(),
+            settings,
+            status: None,
+        }
+    }
+
+    /// Shows `status` along the bottom of the menu, e.g. a problem loading the settings.
+    pub fn set_status(&mut self, status: String) {
+        self.status = Some(status);
+    }
+
+    pub fn run(&mut self) -> Result<(), PongError> {
+        loop {
+            // every choice sets up the terminal its own way, so the menu lets go of it first
+            let action = {
+                let _guard = TerminalGuard::new()?;
+                let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+                self.choose(&mut terminal)?
+            };
+
+            let outcome = match action {
+                Action::Quit => return Ok(()),
+                Action::PlayLocal => play_local(&self.settings, false),
+                Action::PlayCpu => play_local(&self.settings, true),
+                Action::Host => host_match(&self.settings),
+                Action::Join(address) => join_match(&self.settings, &address),
+                Action::WatchReplay(path) => watch_replay(&self.settings, &path),
+            };
+            self.status = Some(match outcome {
+                Ok(message) => message,
+                Err(error) => format!("Error: {}", error),
+            });
+        }
+    }
+
+    fn choose<W: io::Write>(&mut self, terminal: &mut Terminal<CrosstermBackend<W>>) -> Result<Action, PongError> {
+        loop {
+            self.draw(terminal)?;
+            if let Event::Key(key) = event::read()? {
+                if let Some(action) = self.handle_key(key) {
+                    return Ok(action);
+                }
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        // Windows reports releases as well, a key only counts when it goes down
+        if key.kind != KeyEventKind::Press {
+            return None;
+        }
+        if key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL {
+            return Some(Action::Quit);
+        }
+
+        match &mut self.screen {
+            Screen::Main => match key.code {
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.selected = (self.selected + MENU_ITEMS.len() - 1) % MENU_ITEMS.len();
+                },
+                KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1) % MENU_ITEMS.len(),
+                KeyCode::Char('q') | KeyCode::Esc => return Some(Action::Quit),
+                KeyCode::Enter => return self.select(MENU_ITEMS[self.selected]),
+                _ => {},
+            },
+            Screen::Address(purpose) => match key.code {
+                KeyCode::Enter if *purpose == AddressFor::Leaderboard => self.show_leaderboard(),
+                KeyCode::Enter => {
+                    self.screen = Screen::Main;
+                    return Some(Action::Join(self.address.clone()));
+                },
+                KeyCode::Esc => self.screen = Screen::Main,
+                KeyCode::Backspace => {
+                    self.address.pop();
+                },
+                KeyCode::Char(character) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.address.push(character);
+                },
+                _ => {},
+            },
+            Screen::Replays { files, selected } => match key.code {
+                KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => *selected = (*selected + 1).min(files.len().saturating_sub(1)),
+                KeyCode::Enter => return files.get(*selected).cloned().map(Action::WatchReplay),
+                KeyCode::Char('q') | KeyCode::Esc => self.screen = Screen::Main,
+                _ => {},
+            },
+            Screen::Leaderboard { players, selected } => match key.code {
+                KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => *selected = (*selected + 1).min(players.len().saturating_sub(1)),
+                KeyCode::Char('q') | KeyCode::Esc => self.screen = Screen::Main,
+                _ => {},
+            },
+            Screen::Settings { selected, editing: true } => {
+                if let SettingsField::Key(binding) = SETTINGS_FIELDS[*selected] {
+                    // the next key pressed is added to the binding, Esc only backs out
+                    let keys = self.settings.keys.keys_mut(binding);
+                    let key = Key::from_event(&key);
+                    if key != Key::new(KeyCode::Esc, KeyModifiers::NONE) && !keys.contains(&key) {
+                        keys.push(key);
+                    }
+                    self.screen = Screen::Settings { selected: *selected, editing: false };
+                    return None;
+                }
+                if SETTINGS_FIELDS[*selected] == SettingsField::ApiPort {
+                    let port = &mut self.settings.api_port;
+                    match key.code {
+                        KeyCode::Enter | KeyCode::Esc => self.screen = Screen::Settings { selected: *selected, editing: false },
+                        KeyCode::Backspace => *port /= 10,
+                        KeyCode::Char(character) => {
+                            // digits that would take it past the highest port are ignored
+                            let digit = character.to_digit(10).and_then(|digit| u16::try_from(digit).ok());
+                            if let Some(longer) = digit.and_then(|digit| port.checked_mul(10)?.checked_add(digit)) {
+                                *port = longer;
+                            }
+                        },
+                        _ => {},
+                    }
+                    return None;
+                }
+                let (text, max_len) = SETTINGS_FIELDS[*selected].text(&mut self.settings)?;
+                match key.code {
+                    KeyCode::Enter | KeyCode::Esc => self.screen = Screen::Settings { selected: *selected, editing: false },
+                    KeyCode::Backspace => {
+                        text.pop();
+                    },
+                    KeyCode::Char(character)
+                        if !key.modifiers.contains(KeyModifiers::CONTROL) && text.chars().count() < max_len =>
+                    {
+                        text.push(character);
+                    },
+                    _ => {},
+                }
+            },
+            Screen::Settings { selected, editing: false } => {
+                let field = SETTINGS_FIELDS[*selected];
+                match key.code {
+                    KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+                    KeyCode::Down | KeyCode::Char('j') => *selected = (*selected + 1).min(SETTINGS_FIELDS.len() - 1),
+                    KeyCode::Left | KeyCode::Char('h') => self.cycle(field, false),
+                    KeyCode::Right | KeyCode::Char('l') => self.cycle(field, true),
+                    KeyCode::Backspace => {
+                        if let SettingsField::Key(binding) = field {
+                            self.settings.keys.keys_mut(binding).pop();
+                        }
+                    },
+                    KeyCode::Enter if field.is_text() || matches!(field, SettingsField::Key(_)) => {
+                        self.screen = Screen::Settings { selected: *selected, editing: true };
+                    },
+                    KeyCode::Enter => self.cycle(field, true),
+                    KeyCode::Char('q') | KeyCode::Esc => self.leave_settings(),
+                    _ => {},
+                }
+            },
+        }
+        None
+    }
+
+    // the menu takes on a new theme straight away, so it can be seen before saving
+    fn cycle(&mut self, field: SettingsField, forward: bool) {
+        field.cycle(&mut self.settings, &self.themes, forward);
+        if field == SettingsField::Theme {
+            self.theme = self.settings.theme();
+        }
+    }
+
+    // fetches the leaderboard from the HTTP API of the server typed in, on the port set
+    // for it, starting on this player's row
+    fn show_leaderboard(&mut self) {
+        let fetched = self.api_address().and_then(fetch_player_stats);
+        self.screen = match fetched {
+            Ok(players) => {
+                let selected = players.iter().position(|player| player.name == self.settings.player_name).unwrap_or(0);
+                Screen::Leaderboard { players, selected }
+            },
+            Err(error) => {
+                self.status = Some(format!("Error: could not fetch the leaderboard from {}: {}", self.address, error));
+                Screen::Main
+            },
+        };
+    }
+
+    // the address typed in, with or without the port players join on, at the API's port
+    fn api_address(&self) -> Result<SocketAddr, PongError> {
+        let mut addresses = match self.address.to_socket_addrs() {
+            Ok(addresses) => addresses,
+            Err(_) => (self.address.as_str(), self.settings.api_port).to_socket_addrs()?,
+        };
+        let mut address = addresses
+            .next()
+            .ok_or_else(|| PongError::Protocol(format!("{} has no address", self.address)))?;
+        address.set_port(self.settings.api_port);
+        Ok(address)
+    }
+
+    // saves the settings on the way back to the main menu
+    fn leave_settings(&mut self) {
+        if self.settings.player().validate().is_err() {
+            self.settings.player_name = Settings::default().player_name;
+        }
+        // nothing listens on port 0, so a port erased and left blank goes back to the default
+        if self.settings.api_port == 0 {
+            self.settings.api_port = Settings::default().api_port;
+        }
+        self.address = self.settings.server_address.clone();
+        self.screen = Screen::Main;
+        self.status = Some(match self.settings.save() {
+            Ok(()) => String::from("Settings saved"),
+            Err(error) => format!("Error: could not save settings: {}", error),
+        });
+    }
+
+    fn select(&mut self, item: MenuItem) -> Option<Action> {
+        self.status = None;
+        match item {
+            MenuItem::PlayLocal => return Some(Action::PlayLocal),
+            MenuItem::PlayCpu => return Some(Action::PlayCpu),
+            MenuItem::Host => return Some(Action::Host),
+            MenuItem::Join => self.screen = Screen::Address(AddressFor::Join),
+            MenuItem::Replays => match list_replays() {
+                Ok(files) => self.screen = Screen::Replays { files, selected: 0 },
+                Err(error) => self.status = Some(format!("Error: {}", error)),
+            },
+            MenuItem::Leaderboard => self.screen = Screen::Address(AddressFor::Leaderboard),
+            MenuItem::Settings => {
+                // theme files added since the last visit show up too
+                self.themes = Theme::available();
+                self.screen = Screen::Settings { selected: 0, editing: false };
+            },
+            MenuItem::Quit => return Some(Action::Quit),
+        }
+        None
+    }
+
+    fn draw<W: io::Write>(&self, terminal: &mut Terminal<CrosstermBackend<W>>) -> io::Result<()> {
+        let highlight = Style::new().fg(self.theme.highlight).bold();
+        terminal.draw(|frame| {
+            let area = frame.size();
+            frame.render_widget(Block::new().style(self.theme.base()), area);
+            let block = Block::new()
+                .borders(Borders::all())
+                .border_type(BorderType::Rounded)
+                .border_style(Style::new().fg(self.theme.border))
+                .title("RustyPong")
+                .title_alignment(Alignment::Center);
+
+            let hint = match &self.screen {
+                Screen::Main => {
+                    let items: Vec<ListItem> = MENU_ITEMS.iter().map(|item| ListItem::new(item.label())).collect();
+                    let list = List::new(items)
+                        .block(block)
+                        .highlight_symbol("> ")
+                        .highlight_style(highlight);
+                    let mut state = ListState::default();
+                    state.select(Some(self.selected));
+                    frame.render_stateful_widget(list, centered_rect(area, 30, MENU_ITEMS.len() as u16 + 2), &mut state);
+                    "Up/Down to move, Enter to select, q to quit"
+                },
+                Screen::Address(purpose) => {
+                    let prompt = Paragraph::new(vec![
+                        Line::from("Server address"),
+                        Line::from(format!("{}_", self.address)),
+                    ])
+                        .alignment(Alignment::Center)
+                        .block(block);
+                    frame.render_widget(prompt, centered_rect(area, 40, 4));
+                    match purpose {
+                        AddressFor::Join => "Enter to join, Esc to go back",
+                        AddressFor::Leaderboard => "Enter to show its leaderboard, Esc to go back",
+                    }
+                },
+                Screen::Replays { files, .. } if files.is_empty() => {
+                    let empty = Paragraph::new("No replays recorded yet")
+                        .alignment(Alignment::Center)
+                        .block(block);
+                    frame.render_widget(empty, centered_rect(area, 40, 3));
+                    "Esc to go back"
+                },
+                Screen::Replays { files, selected } => {
+                    let items: Vec<ListItem> = files
+                        .iter()
+                        .map(|path| ListItem::new(path.file_name().unwrap_or_default().to_string_lossy().into_owned()))
+                        .collect();
+                    let list = List::new(items)
+                        .block(block)
+                        .highlight_symbol("> ")
+                        .highlight_style(highlight);
+                    let mut state = ListState::default();
+                    state.select(Some(*selected));
+                    let height = (files.len() as u16).saturating_add(2).min(area.height.saturating_sub(4));
+                    frame.render_stateful_widget(list, centered_rect(area, 40, height), &mut state);
+                    "Enter to watch, Esc to go back"
+                },
+                Screen::Leaderboard { players, .. } if players.is_empty() => {
+                    let empty = Paragraph::new("No matches played yet")
+                        .alignment(Alignment::Center)
+                        .block(block);
+                    frame.render_widget(empty, centered_rect(area, 40, 3));
+                    "Esc to go back"
+                },
+                Screen::Leaderboard { players, selected } => {
+                    let header = Row::new(vec!["#", "Player", "Rating", "Won", "Lost"]).style(Style::new().bold());
+                    let rows: Vec<Row> = players
+                        .iter()
+                        .enumerate()
+                        .map(|(index, player)| {
+                            Row::new(vec![
+                                (index + 1).to_string(),
+                                player.name.clone(),
+                                player.rating.to_string(),
+                                player.wins.to_string(),
+                                player.losses.to_string(),
+                            ])
+                        })
+                        .collect();
+                    let table = Table::new(rows)
+                        .header(header)
+                        .widths(&[
+                            Constraint::Length(4),
+                            Constraint::Length(MAX_NAME_LEN as u16),
+                            Constraint::Length(6),
+                            Constraint::Length(4),
+                            Constraint::Length(4),
+                        ])
+                        .block(block.title(block::Title::from("Leaderboard").position(block::Position::Bottom)))
+                        .highlight_symbol("> ")
+                        .highlight_style(highlight);
+                    let mut state = TableState::default();
+                    state.select(Some(*selected));
+                    let height = (players.len() as u16).saturating_add(3).min(area.height.saturating_sub(4));
+                    frame.render_stateful_widget(table, centered_rect(area, 46, height), &mut state);
+                    "Up/Down to scroll, Esc to go back"
+                },
+                Screen::Settings { selected, editing } => {
+                    let rows: Vec<Row> = SETTINGS_FIELDS
+                        .iter()
+                        .enumerate()
+                        .map(|(index, field)| {
+                            let mut value = field.value(&self.settings);
+                            if *editing && index == *selected {
+                                value.push('_');
+                            } else if !field.is_text() && !matches!(field, SettingsField::Key(_)) {
+                                value = format!("< {} >", value);
+                            }
+                            Row::new(vec![field.label().to_string(), value])
+                        })
+                        .collect();
+                    let table = Table::new(rows)
+                        .widths(&[Constraint::Length(16), Constraint::Min(10)])
+                        .block(block.title(block::Title::from("Settings").position(block::Position::Bottom)))
+                        .highlight_symbol("> ")
+                        .highlight_style(highlight);
+                    let mut state = TableState::default();
+                    state.select(Some(*selected));
+                    let height = (SETTINGS_FIELDS.len() as u16 + 2).min(area.height.saturating_sub(4));
+                    frame.render_stateful_widget(table, centered_rect(area, 50, height), &mut state);
+                    match (SETTINGS_FIELDS[*selected], *editing) {
+                        (SettingsField::Key(_), true) => "Press the key to add, Esc to cancel",
+                        (SettingsField::Key(_), false) => "Enter to add a key, Backspace to remove the last, Esc to save and go back",
+                        (_, true) => "Type to edit, Enter when done",
+                        (_, false) => "Left/Right to change, Enter to edit, Esc to save and go back",
+                    }
+                },
+            };
+
+            let mut footer = vec![Line::from(hint)];
+            if let Some(status) = &self.status {
+                footer.insert(0, Line::from(status.as_str()));
+            }
+            let height = (footer.len() as u16).min(area.height);
+            let footer_area = Rect::new(area.x, area.bottom() - height, area.width, height);
+            frame.render_widget(Paragraph::new(footer).alignment(Alignment::Center), footer_area);
+        })?;
+        Ok(())
+    }
+}
+
+/// Plays a match on this keyboard, against the computer if `against_cpu`, and records
+/// it. Returns a line describing where the replay went.
+pub fn play_local(settings: &Settings, against_cpu: bool) -> Result<String, PongError> {
+    let mut term = TerminalOutput::new(ArenaConfig::default(), seed_from_time())?;
+    term.apply_settings(settings);
+    let opponent = if against_cpu {
+        format!("CPU ({})", settings.difficulty.label())
+    } else {
+        String::from("Player 2")
+    };
+    term.set_players([settings.player(), PlayerInfo::new(opponent)]);
+    term.start_recording();
+    if against_cpu {
+        term.run_vs_cpu(settings.difficulty)?;
+    } else {
+        term.run()?;
+    }
+    Ok(replay_saved(term.saved_replay()))
+}
+
+// hosts a match on the default port with this player on the left, running the server on
+// a thread of its own
+fn host_match(settings: &Settings) -> Result<String, PongError> {
+    let arena = ArenaConfig::default();
+    let listener = TcpListener::bind(("0.0.0.0", DEFAULT_PORT))?;
+    let Some((opponent, opponent_info)) = wait_for_opponent(&listener, arena, settings)? else {
+        return Ok(String::from("Stopped hosting"));
+    };
+
+    // connect before the server thread starts, so it cannot be left waiting for a host
+    // that failed to join
+    let stream = TcpStream::connect(("127.0.0.1", DEFAULT_PORT))?;
+    let mut host = listener.accept()?.0;
+    let mut server = TerminalOutput::new(arena, seed_from_time())?;
+    let server_thread = thread::spawn(move || -> Result<Option<PathBuf>, PongError> {
+        let host_info = welcome_client(&mut host, arena, Some(Side::PlayerOne))?;
+        server.set_players([host_info, opponent_info]);
+        server.start_recording();
+        server.run_server(host, opponent, Some(&listener))?;
+        Ok(server.saved_replay().map(Path::to_path_buf))
+    });
+
+    let played = play_online(settings, stream);
+    let replay = server_thread.join().unwrap_or_else(|panic| panic::resume_unwind(panic))?;
+    let result = played?;
+    Ok(format!("{} {}", result, replay_saved(replay.as_deref())))
+}
+
+// shows a waiting screen until a player completes the handshake, returning them and who
+// they are, `None` if the host gives up first
+fn wait_for_opponent(
+    listener: &TcpListener,
+    arena: ArenaConfig,
+    settings: &Settings,
+) -> Result<Option<(TcpStream, PlayerInfo)>, PongError> {
+    let theme = settings.theme();
+    let host = Style::new().fg(settings.player_color.unwrap_or(theme.player_one)).add_modifier(Modifier::BOLD);
+    let _guard = TerminalGuard::new()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    listener.set_nonblocking(true)?;
+    let mut turned_away = String::new();
+
+    loop {
+        terminal.draw(|frame| {
+            let waiting = Paragraph::new(vec![
+                Line::from(vec![
+                    Span::from(format!("Hosting on port {} as ", DEFAULT_PORT)),
+                    Span::styled(settings.player_name.as_str(), host),
+                ]),
+                Line::from("Waiting for an opponent..."),
+                Line::from(turned_away.as_str()),
+                Line::from("Esc to cancel"),
+            ])
+                .alignment(Alignment::Center)
+                .wrap(Wrap { trim: true })
+                .block(
+                    Block::new()
+                        .borders(Borders::all())
+                        .border_type(BorderType::Rounded)
+                        .border_style(Style::new().fg(theme.border))
+                        .title("Host"),
+                );
+            frame.render_widget(Block::new().style(theme.base()), frame.size());
+            frame.render_widget(waiting, centered_rect(frame.size(), 50, 6));
+        })?;
+
+        if event::poll(Duration::from_millis(50))? {
+            if let Event::Key(key) = event::read()? {
+                let cancel = key.kind == KeyEventKind::Press
+                    && (key.code == KeyCode::Esc || (key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL));
+                if cancel {
+                    return Ok(None);
+                }
+            }
+        }
+
+        match listener.accept() {
+            Ok((mut stream, address)) => {
+                stream.set_nonblocking(false)?;
+                match welcome_client(&mut stream, arena, Some(Side::PlayerTwo)) {
+                    Ok(opponent) => {
+                        listener.set_nonblocking(false)?;
+                        return Ok(Some((stream, opponent)));
+                    },
+                    Err(error) => turned_away = format!("Turned away {}: {}", address, error),
+                }
+            },
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {},
+            Err(error) => return Err(error.into()),
+        }
+    }
+}
+
+/// Shows a waiting screen while a ranked server looks for an opponent for `connection`,
+/// returning whether it found one, `false` if the player gave up first. Returns straight
+/// away if the server did not queue the connection.
+pub fn wait_in_queue(settings: &Settings, connection: &mut ServerConnection) -> Result<bool, PongError> {
+    let Some(rating) = connection.queued_rating() else {
+        return Ok(true);
+    };
+    let theme = settings.theme();
+    let player = Style::new().fg(settings.player_color.unwrap_or(theme.player_one)).add_modifier(Modifier::BOLD);
+    let _guard = TerminalGuard::new()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let since = Instant::now();
+
+    loop {
+        let waited = since.elapsed().as_secs();
+        terminal.draw(|frame| {
+            let waiting = Paragraph::new(vec![
+                Line::from(vec![
+                    Span::styled(settings.player_name.as_str(), player),
+                    Span::from(format!(", rated {}", rating)),
+                ]),
+                Line::from(format!("Looking for an opponent... {}:{:02}", waited / 60, waited % 60)),
+                Line::from("Esc to cancel"),
+            ])
+                .alignment(Alignment::Center)
+                .block(
+                    Block::new()
+                        .borders(Borders::all())
+                        .border_type(BorderType::Rounded)
+                        .border_style(Style::new().fg(theme.border))
+                        .title("Ranked"),
+                );
+            frame.render_widget(Block::new().style(theme.base()), frame.size());
+            frame.render_widget(waiting, centered_rect(frame.size(), 50, 5));
+        })?;
+
+        if event::poll(Duration::ZERO)? {
+            if let Event::Key(key) = event::read()? {
+                let cancel = key.kind == KeyEventKind::Press
+                    && (key.code == KeyCode::Esc || (key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL));
+                if cancel {
+                    return Ok(false);
+                }
+            }
+        }
+        if connection.wait_for_match(Duration::from_millis(50))? {
+            return Ok(true);
+        }
+    }
+}
+
+// joins the server at `address`, on the default port unless it names one
+fn join_match(settings: &Settings, address: &str) -> Result<String, PongError> {
+    // an address without a port, a bare IPv6 one included, is joined on the default port
+    let addresses: Vec<SocketAddr> = match address.to_socket_addrs() {
+        Ok(addresses) => addresses.collect(),
+        Err(_) => (address, DEFAULT_PORT).to_socket_addrs()?.collect(),
+    };
+    play_online(settings, TcpStream::connect(&addresses[..])?)
+}
+
+// plays a networked match as a player, returning how it ended
+fn play_online(settings: &Settings, stream: TcpStream) -> Result<String, PongError> {
+    let mut connection = ServerConnection::join(stream, crossterm::terminal::size()?, settings.player())?;
+    if !wait_in_queue(settings, &mut connection)? {
+        return Ok(String::from("Left the ranked queue"));
+    }
+    let mut term = TerminalOutput::new(connection.arena(), 0)?;
+    term.apply_settings(settings);
+    // the server seats anyone joining after both players as a spectator
+    let spectating = connection.side().is_none();
+    if spectating {
+        term.run_spectator(&mut connection)?;
+    } else {
+        term.run_client(&mut connection)?;
+    }
+    Ok(match term.get_match_result() {
+        Some(result) => {
+            let [player_one, player_two] = term.players();
+            result.headline([&player_one.name, &player_two.name])
+        },
+        None if spectating => String::from("Stopped watching."),
+        None => String::from("Left the match."),
+    })
+}
+
+fn watch_replay(settings: &Settings, path: &Path) -> Result<String, PongError> {
+    let mut player = ReplayPlayer::new(Replay::load(path)?)?;
+    player.apply_settings(settings);
+    player.run()?;
+    Ok(format!("Watched {}", path.display()))
+}
+
+// where the last replay went, for the line shown after a match
+fn replay_saved(replay_path: Option<&Path>) -> String {
+    match replay_path {
+        Some(replay_path) => format!("Replay saved to {}", replay_path.display()),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `code` going down and coming back up, as Windows reports every key
+    fn tap(menu: &mut MainMenu, code: KeyCode) -> Vec<Action> {
+        [KeyEventKind::Press, KeyEventKind::Release]
+            .into_iter()
+            .filter_map(|kind| menu.handle_key(KeyEvent::new_with_kind(code, KeyModifiers::NONE, kind)))
+            .collect()
+    }
+
+    #[test]
+    fn a_released_key_does_nothing() {
+        let mut menu = MainMenu::new(Settings::default());
+
+        tap(&mut menu, KeyCode::Down);
+        assert_eq!(menu.selected, 1);
+        tap(&mut menu, KeyCode::Up);
+        assert_eq!(menu.selected, 0);
+        assert!(matches!(tap(&mut menu, KeyCode::Enter).as_slice(), [Action::PlayLocal]));
+    }
+
+    #[test]
+    fn a_new_binding_is_the_key_pressed_after_enter() {
+        let mut menu = MainMenu::new(Settings::default());
+        menu.select(MenuItem::Settings);
+        let field = SETTINGS_FIELDS.iter().position(|field| *field == SettingsField::Key(Binding::PlayerOneUp)).unwrap();
+        menu.screen = Screen::Settings { selected: field, editing: false };
+
+        // the release of the Enter that starts editing is not taken for the new key
+        tap(&mut menu, KeyCode::Enter);
+        tap(&mut menu, KeyCode::Char('x'));
+
+        let keys = [KeyCode::Char('w'), KeyCode::Char('x')].map(|code| Key::new(code, KeyModifiers::NONE));
+        assert_eq!(menu.settings.keys.keys(Binding::PlayerOneUp), keys);
+        assert!(matches!(menu.screen, Screen::Settings { editing: false, .. }));
+    }
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use crate::{Side, TICKS_PER_SECOND};
+
+/// Ticks of countdown between both players agreeing to resume and play continuing.
+pub const RESUME_DELAY_TICKS: u16 = 3 * TICKS_PER_SECOND;
+
+/// Whether the match clock is running. Pausing happens outside the simulation, the
+/// game simply is not ticked, so replays play straight through pauses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PauseState {
+    #[default]
+    Running,
+    /// Frozen until both players vote to resume. Indexed by player one, player two.
+    Paused { resume_votes: [bool; 2] },
+    /// Both players agreed, play continues once the countdown runs out.
+    Resuming { ticks_left: u16 },
+}
+
+impl PauseState {
+    pub fn is_running(&self) -> bool {
+        *self == PauseState::Running
+    }
+
+    /// Pauses the match, or cancels a resume countdown that is already under way.
+    pub fn request_pause(&mut self) {
+        *self = PauseState::Paused { resume_votes: [false; 2] };
+    }
+
+    /// Records that `side` is ready to play, starting the countdown once both are.
+    pub fn vote_resume(&mut self, side: Side) {
+        if let PauseState::Paused { resume_votes } = self {
+            resume_votes[side as usize] = true;
+            if resume_votes.iter().all(|vote| *vote) {
+                *self = PauseState::Resuming { ticks_left: RESUME_DELAY_TICKS };
+            }
+        }
+    }
+
+    /// Counts down a pending resume, returns true when the game should tick.
+    pub fn advance(&mut self) -> bool {
+        match *self {
+            PauseState::Running => true,
+            PauseState::Paused { .. } => false,
+            PauseState::Resuming { ticks_left } if ticks_left > 1 => {
+                *self = PauseState::Resuming { ticks_left: ticks_left - 1 };
+                false
+            },
+            PauseState::Resuming { .. } => {
+                *self = PauseState::Running;
+                true
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn play_resumes_once_both_vote_and_the_countdown_ends() {
+        let mut pause = PauseState::default();
+        pause.request_pause();
+        assert!(!pause.advance());
+
+        // one vote is not enough, and voting twice does not count for the other player
+        pause.vote_resume(Side::PlayerOne);
+        pause.vote_resume(Side::PlayerOne);
+        assert_eq!(pause, PauseState::Paused { resume_votes: [true, false] });
+        pause.vote_resume(Side::PlayerTwo);
+        assert_eq!(pause, PauseState::Resuming { ticks_left: RESUME_DELAY_TICKS });
+
+        for _ in 1..RESUME_DELAY_TICKS {
+            assert!(!pause.advance());
+        }
+        assert!(pause.advance());
+        assert!(pause.is_running());
+    }
+
+    #[test]
+    fn pausing_again_cancels_the_countdown() {
+        let mut pause = PauseState::Resuming { ticks_left: 2 };
+        pause.request_pause();
+        assert_eq!(pause, PauseState::Paused { resume_votes: [false; 2] });
+
+        // a vote while running changes nothing
+        let mut running = PauseState::Running;
+        running.vote_resume(Side::PlayerTwo);
+        assert!(running.is_running());
+    }
+}
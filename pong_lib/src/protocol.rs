@@ -0,0 +1,401 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+use ratatui::style::Color;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use crate::chat::ChatLine;
+use crate::config::{ArenaConfig, ConfigError};
+use crate::error::PongError;
+use crate::history::PlayerStats;
+use crate::settings::MAX_NAME_LEN;
+use crate::{GameDataJSON, PaddleInput, Side};
+
+/// Bumped whenever a message changes incompatibly, both ends must agree on it.
+pub const PROTOCOL_VERSION: u32 = 10;
+
+/// Port the server listens on.
+pub const DEFAULT_PORT: u16 = 3737;
+
+/// How long the server waits for a new connection to introduce itself.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A ping answered later than this counts as lost. TCP delivers every message in the
+/// end, but one this late has missed its moment as far as the player is concerned.
+const PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Most recent pings the loss is worked out over.
+const PING_WINDOW: usize = 10;
+
+/// Longest message, in bytes, either end accepts, well above the largest game state. A
+/// longer one is an error rather than something to keep buffering.
+const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+/// Sent by a client once per tick, the server waits for one from each player before
+/// advancing the game.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ClientMessage {
+    /// First message on a new connection, with the columns and rows the client has to
+    /// draw in and who is connecting.
+    Hello { version: u32, terminal_size: (u16, u16), player: PlayerInfo },
+    Input(PaddleInput),
+    /// Pauses the match, or interrupts a resume countdown.
+    PauseRequest,
+    /// Agrees to continue a paused match.
+    ResumeVote,
+    /// The player is quitting, the match ends in the other player's favour.
+    Leave,
+    /// Asks for a `Pong` with the same number straight away, to time the round trip.
+    /// It does not count as the tick's message.
+    Ping(u32),
+    /// A chat message for everyone in the match. Spectators may send it too, and like a
+    /// ping it does not count as the tick's message.
+    Chat(String),
+    /// Asks to play again once the match is over.
+    RematchVote,
+}
+
+/// Sent by the server once per tick in reply to the players' messages.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum ServerMessage {
+    /// Accepts a `Hello`, telling the client which arena to build and which paddle is
+    /// theirs, `None` for a spectator.
+    Welcome { version: u32, arena: ArenaConfig, side: Option<Side> },
+    /// Accepts a `Hello` on a ranked server, which queues the client at its `rating`
+    /// until it finds an opponent, then sends the `Welcome`.
+    Queued { version: u32, arena: ArenaConfig, rating: i32 },
+    /// Turns a `Hello` away, the server closes the connection after it.
+    Rejected(Rejection),
+    State(GameDataJSON),
+    /// Answers a `Ping`, ahead of the tick's state.
+    Pong(u32),
+    /// A chat message someone sent, or a notice for this client alone, ahead of the
+    /// tick's state.
+    Chat(ChatLine),
+    /// Both players agreed to a rematch, which this player plays on `side`. Comes ahead
+    /// of the new match's first state.
+    Rematch(Side),
+    /// Both players' totals from the server's match history, player one's first, sent
+    /// ahead of the state that ends a match if the server keeps a history.
+    PlayerStats([PlayerStats; 2]),
+}
+
+/// Why the server would not let a client join.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Rejection {
+    /// The client speaks a different protocol version.
+    Version { server_version: u32 },
+    /// The client's terminal cannot display the arena.
+    TerminalTooSmall { needed: (u16, u16), available: (u16, u16) },
+    /// The name the client gave is empty, too long or has control characters in it.
+    InvalidName,
+    /// Someone with the same name is already queued or playing on this ranked server.
+    NameInUse,
+}
+
+/// Who a player is, as they introduced themselves in their `Hello`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PlayerInfo {
+    /// Display name, at most `MAX_NAME_LEN` characters.
+    pub name: String,
+    /// Colour the player's paddle and name are drawn in, instead of the theme's.
+    #[serde(default, with = "crate::theme::color_text::optional")]
+    pub color: Option<Color>,
+}
+
+impl PlayerInfo {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into(), color: None }
+    }
+
+    /// Checks the name can be shown to everyone else without messing up their screen.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let length = self.name.chars().count();
+        let blank = self.name.trim().is_empty();
+        if blank || length > MAX_NAME_LEN || self.name.chars().any(char::is_control) {
+            return Err(ConfigError::InvalidName { name: self.name.clone() });
+        }
+        Ok(())
+    }
+
+    /// This player under a name told apart from `other`'s, numbered "Player 2" if they
+    /// are both called "Player", so the history keeps their matches apart.
+    pub fn distinct_from(mut self, other: &PlayerInfo) -> Self {
+        if self.name == other.name {
+            let name: String = self.name.chars().take(MAX_NAME_LEN - 2).collect();
+            self.name = format!("{} 2", name.trim_end());
+        }
+        self
+    }
+}
+
+/// Writes `message` as a single line of JSON.
+pub fn send_message<W: Write, T: Serialize>(stream: &mut W, message: &T) -> Result<(), PongError> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Reads newline delimited JSON messages off a stream.
+pub struct MessageReader<R> {
+    reader: BufReader<R>,
+    line: Vec<u8>,
+}
+
+impl<R: Read> MessageReader<R> {
+    pub fn new(stream: R) -> Self {
+        Self {
+            reader: BufReader::new(stream),
+            line: Vec::new(),
+        }
+    }
+
+    /// Next message, `PongError::Disconnected` once the other end has closed the connection.
+    /// A read that times out part way through a message picks it up again next call. A
+    /// message longer than `MAX_MESSAGE_LEN` is a protocol error.
+    pub fn read_message<T: DeserializeOwned>(&mut self) -> Result<T, PongError> {
+        let limit = (MAX_MESSAGE_LEN - self.line.len()) as u64;
+        if (&mut self.reader).take(limit).read_until(b'\n', &mut self.line)? == 0 {
+            return Err(PongError::Disconnected);
+        }
+        if self.line.len() >= MAX_MESSAGE_LEN && !self.line.ends_with(b"\n") {
+            self.line.clear();
+            return Err(PongError::Protocol(format!("a message was longer than {} bytes", MAX_MESSAGE_LEN)));
+        }
+        let message = serde_json::from_slice(&self.line);
+        self.line.clear();
+        Ok(message?)
+    }
+}
+
+/// How the connection to the server is doing, measured with pings.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NetStats {
+    /// Time the last answered ping took there and back, `None` before the first answer.
+    pub round_trip: Option<Duration>,
+    /// Share of recent pings lost or answered too late, from 0 to 1.
+    pub loss: f32,
+}
+
+/// A client's connection to the server, past the handshake.
+pub struct ServerConnection {
+    stream: TcpStream,
+    reader: MessageReader<TcpStream>,
+    arena: ArenaConfig,
+    side: Option<Side>,
+    /// The rating a ranked server queued this client at, until it is welcomed.
+    queued_rating: Option<i32>,
+    next_ping: u32,
+    /// Pings not answered yet and when they were sent, oldest first.
+    pending_pings: VecDeque<(u32, Instant)>,
+    /// Whether each of the last few answered pings came back in time, oldest first.
+    ping_results: VecDeque<bool>,
+    round_trip: Option<Duration>,
+}
+
+impl ServerConnection {
+    /// Introduces the client to the server as `player`, reporting the terminal size it
+    /// has to draw in, and learns which arena the match is played in. A ranked server
+    /// may queue the client instead, see `wait_for_match`.
+    pub fn join(mut stream: TcpStream, terminal_size: (u16, u16), player: PlayerInfo) -> Result<Self, PongError> {
+        player.validate()?;
+        let mut reader = MessageReader::new(stream.try_clone()?);
+        let name = player.name.clone();
+        send_message(&mut stream, &ClientMessage::Hello { version: PROTOCOL_VERSION, terminal_size, player })?;
+        let (arena, side, queued_rating) = match reader.read_message()? {
+            ServerMessage::Welcome { version, arena, side } if version == PROTOCOL_VERSION => (arena, side, None),
+            ServerMessage::Queued { version, arena, rating } if version == PROTOCOL_VERSION => (arena, None, Some(rating)),
+            ServerMessage::Welcome { version: server_version, .. }
+            | ServerMessage::Queued { version: server_version, .. }
+            | ServerMessage::Rejected(Rejection::Version { server_version }) => {
+                return Err(PongError::VersionMismatch {
+                    what: "server protocol",
+                    expected: PROTOCOL_VERSION,
+                    found: server_version,
+                });
+            },
+            ServerMessage::Rejected(Rejection::TerminalTooSmall { needed, available }) => {
+                return Err(ConfigError::TerminalTooSmall { needed, available }.into());
+            },
+            ServerMessage::Rejected(Rejection::InvalidName) => return Err(ConfigError::InvalidName { name }.into()),
+            ServerMessage::Rejected(Rejection::NameInUse) => return Err(PongError::NameInUse { name }),
+            message => return Err(PongError::Protocol(format!("expected a welcome, got {:?}", message))),
+        };
+        Ok(Self {
+            stream,
+            reader,
+            arena,
+            side,
+            queued_rating,
+            next_ping: 0,
+            pending_pings: VecDeque::new(),
+            ping_results: VecDeque::new(),
+            round_trip: None,
+        })
+    }
+
+    /// The arena the server picked for the match.
+    pub fn arena(&self) -> ArenaConfig {
+        self.arena
+    }
+
+    /// The paddle this client plays, `None` when it joined as a spectator. Players
+    /// swap sides for a rematch.
+    pub fn side(&self) -> Option<Side> {
+        self.side
+    }
+
+    /// The rating the server queued this client at, while it waits for an opponent.
+    pub fn queued_rating(&self) -> Option<i32> {
+        self.queued_rating
+    }
+
+    /// Waits up to `timeout` for a ranked server to find an opponent, returning whether
+    /// it has and the match can start. Returns straight away when not queued.
+    pub fn wait_for_match(&mut self, timeout: Duration) -> Result<bool, PongError> {
+        if self.queued_rating.is_none() {
+            return Ok(true);
+        }
+        self.stream.set_read_timeout(Some(timeout))?;
+        let message = self.reader.read_message();
+        self.stream.set_read_timeout(None)?;
+        match message {
+            Ok(ServerMessage::Welcome { side, .. }) => {
+                self.side = side;
+                self.queued_rating = None;
+                Ok(true)
+            },
+            Ok(message) => Err(PongError::Protocol(format!("expected a welcome, got {:?}", message))),
+            Err(PongError::Io(error)) if matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                Ok(false)
+            },
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) -> Result<(), PongError> {
+        send_message(&mut self.stream, message)
+    }
+
+    /// Next message from the server. Answers to pings are taken care of here rather
+    /// than returned, a rematch is returned after switching `side`.
+    pub fn receive(&mut self) -> Result<ServerMessage, PongError> {
+        loop {
+            match self.reader.read_message()? {
+                ServerMessage::Pong(number) => self.record_pong(number),
+                ServerMessage::Rematch(side) => {
+                    self.side = Some(side);
+                    return Ok(ServerMessage::Rematch(side));
+                },
+                message => return Ok(message),
+            }
+        }
+    }
+
+    /// Sends a ping, the answer shows up in `net_stats` once `receive` reads it.
+    pub fn ping(&mut self) -> Result<(), PongError> {
+        let number = self.next_ping;
+        self.next_ping = self.next_ping.wrapping_add(1);
+        self.pending_pings.push_back((number, Instant::now()));
+        self.send(&ClientMessage::Ping(number))
+    }
+
+    pub fn net_stats(&self) -> NetStats {
+        // a ping still waiting past the timeout is lost already, whenever it comes back
+        let overdue = self.pending_pings.iter().filter(|(_, sent)| sent.elapsed() > PING_TIMEOUT).count();
+        let lost = self.ping_results.iter().filter(|in_time| !**in_time).count() + overdue;
+        let counted = self.ping_results.len() + overdue;
+        NetStats {
+            round_trip: self.round_trip,
+            loss: if counted == 0 { 0.0 } else { lost as f32 / counted as f32 },
+        }
+    }
+
+    fn record_pong(&mut self, number: u32) {
+        // answers come back in order, so anything sent before this ping never will
+        while let Some((pending, sent)) = self.pending_pings.pop_front() {
+            let round_trip = sent.elapsed();
+            let answered = pending == number;
+            self.ping_results.push_back(answered && round_trip <= PING_TIMEOUT);
+            if self.ping_results.len() > PING_WINDOW {
+                self.ping_results.pop_front();
+            }
+            if answered {
+                self.round_trip = Some(round_trip);
+                break;
+            }
+        }
+    }
+}
+
+/// Server side of `ServerConnection::join`: welcomes the client into a match played in
+/// `arena`, as the player on `side` or as a spectator, or turns it away. Returns who the
+/// client says they are.
+pub fn welcome_client(stream: &mut TcpStream, arena: ArenaConfig, side: Option<Side>) -> Result<PlayerInfo, PongError> {
+    let player = greet_client(stream, arena)?;
+    send_message(stream, &ServerMessage::Welcome { version: PROTOCOL_VERSION, arena, side })?;
+    Ok(player)
+}
+
+/// Reads a new client's `Hello` and turns it away if it cannot play in `arena`, leaving
+/// the server to send the `Welcome` or `Queued`. Returns who the client says they are.
+/// The client waits for the reply before sending anything else, so nothing is lost when
+/// the reader used here is dropped.
+pub fn greet_client(stream: &mut TcpStream, arena: ArenaConfig) -> Result<PlayerInfo, PongError> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let hello = MessageReader::new(stream.try_clone()?).read_message();
+    stream.set_read_timeout(None)?;
+
+    let ClientMessage::Hello { version, terminal_size, player } = hello? else {
+        return Err(PongError::Protocol(String::from("expected a hello")));
+    };
+    // explain a rejection so the client can tell its user why it was turned away
+    if version != PROTOCOL_VERSION {
+        send_message(stream, &ServerMessage::Rejected(Rejection::Version { server_version: PROTOCOL_VERSION }))?;
+        return Err(PongError::VersionMismatch {
+            what: "client protocol",
+            expected: PROTOCOL_VERSION,
+            found: version,
+        });
+    }
+    if let Err(error) = arena.check_fits(terminal_size) {
+        let needed = arena.min_terminal_size();
+        send_message(stream, &ServerMessage::Rejected(Rejection::TerminalTooSmall { needed, available: terminal_size }))?;
+        return Err(error.into());
+    }
+    if let Err(error) = player.validate() {
+        send_message(stream, &ServerMessage::Rejected(Rejection::InvalidName))?;
+        return Err(error.into());
+    }
+    Ok(player)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_repeated_name_is_numbered() {
+        let player = PlayerInfo::new("Player");
+        assert_eq!(PlayerInfo::new("Player").distinct_from(&player).name, "Player 2");
+        assert_eq!(PlayerInfo::new("Ann").distinct_from(&player).name, "Ann");
+
+        let long = PlayerInfo::new("a".repeat(MAX_NAME_LEN));
+        let numbered = long.clone().distinct_from(&long);
+        assert_ne!(numbered.name, long.name);
+        assert!(numbered.validate().is_ok());
+    }
+
+    #[test]
+    fn an_endless_line_is_cut_off() {
+        let mut wire = Vec::new();
+        send_message(&mut wire, &ClientMessage::Ping(7)).unwrap();
+        wire.resize(wire.len() + MAX_MESSAGE_LEN * 2, b'a');
+        let mut reader = MessageReader::new(wire.as_slice());
+
+        assert_eq!(reader.read_message::<ClientMessage>().unwrap(), ClientMessage::Ping(7));
+        assert!(matches!(reader.read_message::<ClientMessage>(), Err(PongError::Protocol(_))));
+    }
+}
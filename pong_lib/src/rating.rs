@@ -0,0 +1,66 @@
+use crate::Side;
+
+/// Rating every player starts on before their first match.
+pub const DEFAULT_RATING: i32 = 1500;
+
+/// Most a single match can move a rating.
+const K_FACTOR: f64 = 32.0;
+
+/// Chance of `rating` beating `opponent`, from 0 to 1, under the Elo model: 400 points
+/// apart the better player is expected to win ten times as often.
+pub fn expected_score(rating: i32, opponent: i32) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) as f64 / 400.0))
+}
+
+/// How much a match moves each player's rating, player one's first. What one player
+/// gains the other loses, a draw moves the ratings toward each other.
+pub fn rating_changes(ratings: [i32; 2], winner: Option<Side>) -> [i32; 2] {
+    let score = match winner {
+        Some(Side::PlayerOne) => 1.0,
+        Some(Side::PlayerTwo) => 0.0,
+        None => 0.5,
+    };
+    let change = (K_FACTOR * (score - expected_score(ratings[0], ratings[1]))).round() as i32;
+    [change, -change]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changes_are_zero_sum() {
+        for ratings in [[1500, 1500], [1700, 1300], [1200, 1850]] {
+            for winner in [Some(Side::PlayerOne), Some(Side::PlayerTwo), None] {
+                let [one, two] = rating_changes(ratings, winner);
+                assert_eq!(one + two, 0, "{:?} won at {:?}", winner, ratings);
+            }
+        }
+    }
+
+    #[test]
+    fn winning_gains_more_against_a_better_player() {
+        assert_eq!(rating_changes([1500, 1500], Some(Side::PlayerOne)), [16, -16]);
+        assert_eq!(rating_changes([1500, 1500], Some(Side::PlayerTwo)), [-16, 16]);
+        let [upset, _] = rating_changes([1300, 1700], Some(Side::PlayerOne));
+        let [expected, _] = rating_changes([1700, 1300], Some(Side::PlayerOne));
+        assert!(upset > 16 && expected < 16);
+    }
+
+    #[test]
+    fn draws_move_ratings_together() {
+        assert_eq!(rating_changes([1500, 1500], None), [0, 0]);
+        let [lower, higher] = rating_changes([1400, 1600], None);
+        assert!(lower > 0 && higher < 0);
+    }
+
+    #[test]
+    fn expected_scores_are_symmetric() {
+        for (rating, opponent) in [(1500, 1500), (1600, 1400), (1000, 2000)] {
+            let total = expected_score(rating, opponent) + expected_score(opponent, rating);
+            assert!((total - 1.0).abs() < 1e-9);
+        }
+        assert_eq!(expected_score(1500, 1500), 0.5);
+        assert!((expected_score(1900, 1500) - 10.0 / 11.0).abs() < 1e-9);
+    }
+}
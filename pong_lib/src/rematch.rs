@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use crate::Side;
+
+/// Whether the players want to play again once a match is over. Like a pause, the vote
+/// happens outside the simulation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum RematchVote {
+    /// Waiting on the players. Indexed by player one, player two.
+    Open { accepted: [bool; 2] },
+    /// This player left instead, so there is no rematch.
+    Declined(Side),
+}
+
+impl Default for RematchVote {
+    fn default() -> Self {
+        RematchVote::Open { accepted: [false; 2] }
+    }
+}
+
+impl RematchVote {
+    /// Records that `side` wants a rematch. Does nothing once someone has declined.
+    pub fn accept(&mut self, side: Side) {
+        if let RematchVote::Open { accepted } = self {
+            accepted[side as usize] = true;
+        }
+    }
+
+    /// Records that `side` left, which settles the vote for good.
+    pub fn decline(&mut self, side: Side) {
+        if let RematchVote::Open { .. } = self {
+            *self = RematchVote::Declined(side);
+        }
+    }
+
+    /// Whether both players want a rematch.
+    pub fn agreed(&self) -> bool {
+        *self == RematchVote::Open { accepted: [true; 2] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_rematch_needs_both_players() {
+        let mut vote = RematchVote::default();
+        vote.accept(Side::PlayerTwo);
+        vote.accept(Side::PlayerTwo);
+        assert!(!vote.agreed());
+        vote.accept(Side::PlayerOne);
+        assert!(vote.agreed());
+    }
+
+    #[test]
+    fn a_decline_ends_the_vote() {
+        let mut vote = RematchVote::default();
+        vote.accept(Side::PlayerOne);
+        vote.decline(Side::PlayerTwo);
+        vote.accept(Side::PlayerTwo);
+        vote.decline(Side::PlayerOne);
+        assert_eq!(vote, RematchVote::Declined(Side::PlayerTwo));
+        assert!(!vote.agreed());
+    }
+}
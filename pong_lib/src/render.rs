@@ -0,0 +1,105 @@
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::symbols::Marker;
+use ratatui::widgets::canvas::{Canvas, Points};
+use ratatui::Frame;
+use serde::{Deserialize, Serialize};
+use crate::collision::Vec2;
+
+/// How the paddles and ball are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Renderer {
+    /// Whole character cells, paddles as boxes and the ball as an `o`.
+    #[default]
+    Cells,
+    /// Two pixels to a cell, one above the other.
+    HalfBlock,
+    /// Eight dots to a cell, two across and four down. Needs a font with the Unicode
+    /// Braille Patterns.
+    Braille,
+}
+
+impl Renderer {
+    pub const ALL: [Renderer; 3] = [Renderer::Cells, Renderer::HalfBlock, Renderer::Braille];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Renderer::Cells => "Cells",
+            Renderer::HalfBlock => "Half blocks",
+            Renderer::Braille => "Braille",
+        }
+    }
+
+    // the canvas marker and the pixels it fits in one cell, across and down
+    fn marker(&self) -> Option<(Marker, u16, u16)> {
+        match self {
+            Renderer::Cells => None,
+            Renderer::HalfBlock => Some((Marker::HalfBlock, 1, 2)),
+            Renderer::Braille => Some((Marker::Braille, 2, 4)),
+        }
+    }
+}
+
+/// Something solid to paint, in logical arena units.
+pub(crate) struct Shape {
+    pub position: Vec2,
+    pub size: (u16, u16),
+    pub color: Color,
+}
+
+/// Paints `shapes` on `background` over `area`, the screen cells inside the border of
+/// `arena`, at the renderer's resolution. The ball's position between cells shows as it
+/// moves. Returns false for `Renderer::Cells`, which leaves the drawing to whole cells.
+pub(crate) fn paint(
+    frame: &mut Frame,
+    renderer: Renderer,
+    arena: Rect,
+    area: Rect,
+    shapes: &[Shape],
+    background: Color,
+) -> bool {
+    let Some((marker, across, down)) = renderer.marker() else {
+        return false;
+    };
+    let left = (arena.x + 1) as f64;
+    let top = (arena.y + 1) as f64;
+    let width = arena.width.saturating_sub(2).max(1) as f64;
+    let height = arena.height.saturating_sub(2).max(1) as f64;
+    let columns = (area.width * across).max(2) as usize;
+    let rows = (area.height * down).max(2) as usize;
+
+    // the canvas puts pixel `i` of `n` at `i / (n - 1)` of the way along its bounds, but
+    // whether a pixel is lit depends on the logical area it covers, `i / n` to `(i + 1) / n`
+    let mut points: Vec<(Vec<(f64, f64)>, Color)> = Vec::new();
+    for shape in shapes {
+        let covers = |pixel: usize, pixels: usize, start: f64, span: f64, low: f32, size: u16| {
+            let middle = start + (pixel as f64 + 0.5) * span / pixels as f64;
+            middle >= low as f64 && middle < low as f64 + size as f64
+        };
+        let mut coords = Vec::new();
+        for column in (0..columns).filter(|&column| covers(column, columns, left, width, shape.position.x, shape.size.0)) {
+            for row in (0..rows).filter(|&row| covers(row, rows, top, height, shape.position.y, shape.size.1)) {
+                // aiming at the middle of the pixel keeps rounding from tipping it into the last
+                let x = (column as f64 + 0.5).min((columns - 1) as f64) * width / (columns - 1) as f64;
+                let y = height - (row as f64 + 0.5).min((rows - 1) as f64) * height / (rows - 1) as f64;
+                coords.push((x, y));
+            }
+        }
+        // a pixel in the terminal's own colour counts as unlit, white stands in for it
+        let color = if shape.color == Color::Reset { Color::White } else { shape.color };
+        points.push((coords, color));
+    }
+
+    let canvas = Canvas::default()
+        .marker(marker)
+        .background_color(background)
+        .x_bounds([0.0, width])
+        .y_bounds([0.0, height])
+        .paint(|context| {
+            for (coords, color) in &points {
+                context.draw(&Points { coords, color: *color });
+            }
+        });
+    frame.render_widget(canvas, area);
+    true
+}
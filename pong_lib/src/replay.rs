@@ -0,0 +1,359 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, stdout, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::prelude::{CrosstermBackend, Terminal};
+use serde::{Deserialize, Serialize};
+use crate::chat::{ChatLine, ChatPanel};
+use crate::config::ArenaConfig;
+use crate::error::PongError;
+use crate::hud::clock;
+use crate::protocol::PlayerInfo;
+use crate::rules::MatchRules;
+use crate::serve::ServeRules;
+use crate::settings::Settings;
+use crate::terminal::TerminalGuard;
+use crate::{GameDataJSON, PaddleInput, Side, TerminalOutput, TICKS_PER_SECOND};
+
+/// Bumped whenever the replay file layout or the simulation changes incompatibly.
+pub const REPLAY_VERSION: u32 = 7;
+
+/// Ticks between the snapshots stored alongside the inputs.
+pub const SNAPSHOT_INTERVAL: u32 = 5 * TICKS_PER_SECOND as u32;
+
+/// Directory matches are recorded into, relative to where the game is started.
+pub const REPLAY_DIR: &str = "replays";
+
+const SEEK_TICKS: u32 = 5 * TICKS_PER_SECOND as u32;
+const PLAYBACK_SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+/// Everything needed to re-run a match: the simulation is deterministic, so the seed,
+/// configuration and per-tick inputs reproduce it exactly. The periodic snapshots let
+/// playback detect a replay recorded by an incompatible build.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub arena: ArenaConfig,
+    pub serve_rules: ServeRules,
+    pub match_rules: MatchRules,
+    /// Who played, player one first.
+    pub players: [PlayerInfo; 2],
+    /// Player one and player two input for every tick, in order.
+    pub inputs: Vec<[PaddleInput; 2]>,
+    /// Snapshot after every `SNAPSHOT_INTERVAL`th tick, keyed by the tick count.
+    pub snapshots: Vec<(u32, GameDataJSON)>,
+    /// Chat during the match, in the order it was sent.
+    #[serde(default)]
+    pub chat: Vec<ChatLine>,
+    /// The player who forfeited, after the last input, if the match ended that way.
+    #[serde(default)]
+    pub forfeit: Option<Side>,
+}
+
+impl Replay {
+    pub fn new(
+        seed: u64,
+        arena: ArenaConfig,
+        serve_rules: ServeRules,
+        match_rules: MatchRules,
+        players: [PlayerInfo; 2],
+    ) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            arena,
+            serve_rules,
+            match_rules,
+            players,
+            inputs: Vec::new(),
+            snapshots: Vec::new(),
+            chat: Vec::new(),
+            forfeit: None,
+        }
+    }
+
+    pub fn record(&mut self, inputs: [PaddleInput; 2], game_data: &GameDataJSON) {
+        self.inputs.push(inputs);
+        let tick = self.inputs.len() as u32;
+        if tick.is_multiple_of(SNAPSHOT_INTERVAL) {
+            self.snapshots.push((tick, game_data.clone()));
+        }
+    }
+
+    pub fn tick_count(&self) -> u32 {
+        self.inputs.len() as u32
+    }
+
+    /// Writes the replay to `path`, failing if a file is there already.
+    pub fn save(&self, path: &Path) -> Result<(), PongError> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let writer = BufWriter::new(OpenOptions::new().write(true).create_new(true).open(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, PongError> {
+        let reader = BufReader::new(File::open(path)?);
+        let replay: Replay = serde_json::from_reader(reader)?;
+        if replay.version != REPLAY_VERSION {
+            return Err(PongError::VersionMismatch {
+                what: "replay",
+                expected: REPLAY_VERSION,
+                found: replay.version,
+            });
+        }
+        Ok(replay)
+    }
+}
+
+/// Where a match played from `seed` and finishing now gets recorded, e.g.
+/// `replays/match-1700000000-000123456-00000000000004d2.json`. The seed tells apart
+/// matches a server finishes at the same moment.
+pub fn default_replay_path(seed: u64) -> PathBuf {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let name = format!("match-{}-{:09}-{:016x}.json", elapsed.as_secs(), elapsed.subsec_nanos(), seed);
+    PathBuf::from(REPLAY_DIR).join(name)
+}
+
+/// Recorded replays, newest first. Empty if nothing has been recorded yet.
+pub fn list_replays() -> io::Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(REPLAY_DIR) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    let mut replays = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "json") {
+            replays.push(path);
+        }
+    }
+    // the timestamp in the name sorts them by when they were recorded
+    replays.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(replays)
+}
+
+/// Plays a `Replay` back through `TerminalOutput::draw`, re-simulating it tick by tick.
+pub struct ReplayPlayer {
+    replay: Replay,
+    term: TerminalOutput,
+    tick: u32,
+    paused: bool,
+    speed_index: usize,
+    desync_tick: Option<u32>,
+    /// Re-applied to the game whenever seeking back rebuilds it.
+    settings: Settings,
+    /// Index of the first line of `replay.chat` not shown yet.
+    next_chat: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Result<Self, PongError> {
+        let term = Self::build_game(&replay)?;
+        Ok(Self {
+            replay,
+            term,
+            tick: 0,
+            paused: false,
+            speed_index: 2,
+            desync_tick: None,
+            settings: Settings::default(),
+            next_chat: 0,
+        })
+    }
+
+    fn build_game(replay: &Replay) -> Result<TerminalOutput, PongError> {
+        let mut term = TerminalOutput::new(replay.arena, replay.seed)?;
+        term.set_serve_rules(replay.serve_rules);
+        term.set_match_rules(replay.match_rules)?;
+        term.set_players(replay.players.clone());
+        if !replay.chat.is_empty() {
+            term.chat = Some(ChatPanel::read_only());
+        }
+        Ok(term)
+    }
+
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.settings = settings.clone();
+        self.term.apply_settings(settings);
+    }
+
+    fn speed(&self) -> f32 {
+        PLAYBACK_SPEEDS[self.speed_index]
+    }
+
+    // advances one tick, returns false at the end of the recording
+    fn step_forward(&mut self) -> bool {
+        let Some(&[player_one, player_two]) = self.replay.inputs.get(self.tick as usize) else {
+            self.apply_forfeit();
+            return false;
+        };
+        let game_data = self.term.step(player_one, player_two);
+        self.tick += 1;
+        let elapsed = self.term.game_state.elapsed_ticks;
+        while let Some(line) = self.replay.chat.get(self.next_chat).filter(|line| line.tick <= elapsed) {
+            self.term.receive_chat(line.clone());
+            self.next_chat += 1;
+        }
+
+        if let Ok(index) = self.replay.snapshots.binary_search_by_key(&self.tick, |(tick, _)| *tick) {
+            let recorded = serde_json::to_string(&self.replay.snapshots[index].1).ok();
+            if recorded != serde_json::to_string(&game_data).ok() && self.desync_tick.is_none() {
+                self.desync_tick = Some(self.tick);
+            }
+        }
+        if self.tick == self.replay.tick_count() {
+            self.apply_forfeit();
+        }
+        true
+    }
+
+    // a forfeit comes between ticks, so it ends the match after the last one recorded
+    fn apply_forfeit(&mut self) {
+        if let Some(side) = self.replay.forfeit {
+            self.term.forfeit(side);
+        }
+    }
+
+    /// Jumps to `tick`. Going backwards re-simulates from the start of the match.
+    pub fn seek(&mut self, tick: u32) -> Result<(), PongError> {
+        let tick = tick.min(self.replay.tick_count());
+        if tick < self.tick {
+            self.term = Self::build_game(&self.replay)?;
+            self.term.apply_settings(&self.settings);
+            self.tick = 0;
+            self.next_chat = 0;
+        }
+        while self.tick < tick && self.step_forward() {}
+        Ok(())
+    }
+
+    fn status_line(&self) -> String {
+        let mut status = format!(
+            " {} {}x | {} / {} | [space] play/pause [←/→] seek [,/.] step [-/+] speed [q] quit ",
+            if self.paused { "||" } else { ">" },
+            self.speed(),
+            clock(self.tick),
+            clock(self.replay.tick_count()),
+        );
+        if let Some(tick) = self.desync_tick {
+            status.push_str(&format!("| out of sync at {} ", clock(tick)));
+        }
+        status
+    }
+
+    pub fn run(&mut self) -> Result<(), PongError> {
+        let _guard = TerminalGuard::new()?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+        let mut next_tick = Instant::now();
+
+        loop {
+            self.term.footer = Some(self.status_line());
+            self.term.draw_frame(&mut terminal)?;
+
+            let timeout = next_tick.saturating_duration_since(Instant::now());
+            if event::poll(timeout)? {
+                if let Event::Key(key) = event::read()? {
+                    if !self.handle_key(key)? {
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            let tick_length = Duration::from_secs_f32(1.0 / (TICKS_PER_SECOND as f32 * self.speed()));
+            next_tick = Instant::now() + tick_length;
+            if !self.paused && !self.step_forward() {
+                self.paused = true;
+            }
+        }
+        Ok(())
+    }
+
+    // returns false when the viewer quits
+    fn handle_key(&mut self, key: KeyEvent) -> Result<bool, PongError> {
+        // Windows reports releases as well, a key only counts when it goes down
+        if key.kind != KeyEventKind::Press {
+            return Ok(true);
+        }
+        match key.code {
+            KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => return Ok(false),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char(' ') => self.paused = !self.paused,
+            KeyCode::Left => self.seek(self.tick.saturating_sub(SEEK_TICKS))?,
+            KeyCode::Right => self.seek(self.tick.saturating_add(SEEK_TICKS))?,
+            KeyCode::Home => self.seek(0)?,
+            KeyCode::Char(',') => {
+                self.paused = true;
+                self.seek(self.tick.saturating_sub(1))?;
+            },
+            KeyCode::Char('.') => {
+                self.paused = true;
+                self.step_forward();
+            },
+            KeyCode::Char('-') => self.speed_index = self.speed_index.saturating_sub(1),
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                self.speed_index = (self.speed_index + 1).min(PLAYBACK_SPEEDS.len() - 1);
+            },
+            _ => {},
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::MatchEnd;
+
+    // a few idle ticks of a match between two players called A and B
+    fn idle_replay() -> Replay {
+        let players = [PlayerInfo::new("A"), PlayerInfo::new("B")];
+        let mut replay = Replay::new(0, ArenaConfig::default(), ServeRules::default(), MatchRules::default(), players);
+        replay.inputs = vec![[PaddleInput::Idle; 2]; 10];
+        replay
+    }
+
+    // `code` going down and coming back up, as Windows reports every key
+    fn tap(player: &mut ReplayPlayer, code: KeyCode) {
+        for kind in [KeyEventKind::Press, KeyEventKind::Release] {
+            assert!(player.handle_key(KeyEvent::new_with_kind(code, KeyModifiers::NONE, kind)).unwrap());
+        }
+    }
+
+    #[test]
+    fn a_released_key_does_nothing() {
+        let mut player = ReplayPlayer::new(idle_replay()).unwrap();
+
+        tap(&mut player, KeyCode::Char(' '));
+        assert!(player.paused);
+        tap(&mut player, KeyCode::Char('.'));
+        assert_eq!(player.tick, 1);
+        tap(&mut player, KeyCode::Char('+'));
+        assert_eq!(player.speed(), 2.0);
+    }
+
+    #[test]
+    fn a_forfeit_ends_the_replay() {
+        let mut term = TerminalOutput::new(ArenaConfig::default(), 0).unwrap();
+        term.start_recording();
+        for _ in 0..10 {
+            term.step(PaddleInput::Up, PaddleInput::Idle);
+        }
+        term.forfeit(Side::PlayerOne);
+        let replay = term.take_recording().unwrap();
+        assert_eq!(replay.forfeit, Some(Side::PlayerOne));
+
+        let mut player = ReplayPlayer::new(replay).unwrap();
+        player.seek(9).unwrap();
+        assert_eq!(player.term.game_state.match_result, None);
+        player.seek(10).unwrap();
+        let result = player.term.game_state.match_result.unwrap();
+        assert_eq!((result.reason, result.winner), (MatchEnd::Forfeit(Side::PlayerOne), Some(Side::PlayerTwo)));
+    }
+}
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use crate::config::ConfigError;
+use crate::events::GameEvent;
+use crate::{Side, TICKS_PER_SECOND};
+
+/// How a match is scored and when it ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MatchRules {
+    /// Points needed to take a game.
+    pub points_per_game: u16,
+    /// Lead a player needs over the other to take a game once they reach `points_per_game`.
+    pub win_by: u16,
+    /// The match is best-of this many games.
+    pub games: u16,
+    /// Ends the match after this many seconds, whatever the score.
+    pub time_limit_secs: Option<u16>,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            points_per_game: 11,
+            win_by: 2,
+            games: 1,
+            time_limit_secs: None,
+        }
+    }
+}
+
+impl MatchRules {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.points_per_game == 0 {
+            return Err(ConfigError::InvalidRules { reason: "points per game must be at least 1" });
+        }
+        if self.win_by == 0 {
+            return Err(ConfigError::InvalidRules { reason: "win-by margin must be at least 1" });
+        }
+        if self.games == 0 {
+            return Err(ConfigError::InvalidRules { reason: "a match needs at least 1 game" });
+        }
+        if self.time_limit_secs == Some(0) {
+            return Err(ConfigError::InvalidRules { reason: "time limit must be at least 1 second" });
+        }
+        Ok(())
+    }
+
+    /// Games a player must take to win the match outright.
+    pub fn games_to_win(&self) -> u16 {
+        self.games / 2 + 1
+    }
+
+    pub fn time_limit_ticks(&self) -> Option<u32> {
+        self.time_limit_secs.map(|secs| secs as u32 * TICKS_PER_SECOND as u32)
+    }
+
+    /// Winner of the current game given both players' points, if it has been decided.
+    pub fn game_winner(&self, player_one_points: u16, player_two_points: u16) -> Option<Side> {
+        let (leader, leading, trailing) = if player_one_points >= player_two_points {
+            (Side::PlayerOne, player_one_points, player_two_points)
+        } else {
+            (Side::PlayerTwo, player_two_points, player_one_points)
+        };
+
+        if leading >= self.points_per_game && leading - trailing >= self.win_by {
+            Some(leader)
+        } else {
+            None
+        }
+    }
+}
+
+/// Why a match finished.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MatchEnd {
+    /// A player took enough games.
+    GamesWon,
+    /// The time limit ran out.
+    TimeLimit,
+    /// This player left or lost their connection, the other wins.
+    Forfeit(Side),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MatchResult {
+    /// `None` when the time limit ran out on a level score.
+    pub winner: Option<Side>,
+    pub reason: MatchEnd,
+    pub player_one_games: u16,
+    pub player_two_games: u16,
+    /// Points in the last game played.
+    pub player_one_score: u16,
+    pub player_two_score: u16,
+    pub elapsed_ticks: u32,
+    pub stats: MatchStats,
+}
+
+impl MatchResult {
+    /// Who won, calling the players by `names`, player one's first.
+    pub fn headline(&self, names: [&str; 2]) -> String {
+        match self.winner {
+            Some(side) => format!("{} wins!", names[side as usize]),
+            None => String::from("It's a draw!"),
+        }
+    }
+
+    /// Explains a result that was not decided on the court.
+    pub fn detail(&self, names: [&str; 2]) -> Option<String> {
+        match self.reason {
+            MatchEnd::Forfeit(side) => Some(format!("{} left the match", names[side as usize])),
+            MatchEnd::GamesWon | MatchEnd::TimeLimit => None,
+        }
+    }
+}
+
+/// Totals kept over a match for the results screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MatchStats {
+    /// Times each player hit the ball, player one's first.
+    pub hits: [u32; 2],
+    /// Points each player scored over all the games, player one's first.
+    pub points: [u32; 2],
+    /// Most paddle hits in one rally.
+    pub longest_rally: u32,
+    /// Paddle hits since the last serve.
+    rally: u32,
+}
+
+impl MatchStats {
+    pub fn record(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::PaddleHit { side } => {
+                self.hits[*side as usize] = self.hits[*side as usize].saturating_add(1);
+                self.rally = self.rally.saturating_add(1);
+                self.longest_rally = self.longest_rally.max(self.rally);
+            },
+            GameEvent::PointScored { scorer } => {
+                self.points[*scorer as usize] = self.points[*scorer as usize].saturating_add(1);
+                self.rally = 0;
+            },
+            GameEvent::WallBounce { .. } | GameEvent::GameWon { .. } | GameEvent::MatchOver(_) => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_game_needs_the_points_and_the_margin() {
+        let rules = MatchRules::default();
+        assert_eq!(rules.game_winner(10, 0), None);
+        assert_eq!(rules.game_winner(11, 9), Some(Side::PlayerOne));
+        // level at 10, play goes on until someone is two clear
+        assert_eq!(rules.game_winner(11, 10), None);
+        assert_eq!(rules.game_winner(12, 11), None);
+        assert_eq!(rules.game_winner(12, 10), Some(Side::PlayerOne));
+        assert_eq!(rules.game_winner(14, 16), Some(Side::PlayerTwo));
+
+        let sudden_death = MatchRules { win_by: 1, ..rules };
+        assert_eq!(sudden_death.game_winner(11, 10), Some(Side::PlayerOne));
+    }
+
+    #[test]
+    fn a_match_is_won_with_most_of_its_games() {
+        let games_to_win = |games| MatchRules { games, ..MatchRules::default() }.games_to_win();
+        assert_eq!(games_to_win(1), 1);
+        assert_eq!(games_to_win(3), 2);
+        assert_eq!(games_to_win(4), 3);
+        assert_eq!(games_to_win(5), 3);
+    }
+
+    #[test]
+    fn zeroes_are_rejected() {
+        assert!(MatchRules::default().validate().is_ok());
+        for rules in [
+            MatchRules { points_per_game: 0, ..MatchRules::default() },
+            MatchRules { win_by: 0, ..MatchRules::default() },
+            MatchRules { games: 0, ..MatchRules::default() },
+            MatchRules { time_limit_secs: Some(0), ..MatchRules::default() },
+        ] {
+            assert!(rules.validate().is_err(), "{:?}", rules);
+        }
+    }
+}
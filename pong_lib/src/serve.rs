@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use crate::collision::Vec2;
+use crate::rng::SimRng;
+use crate::{Side, TICKS_PER_SECOND};
+
+/// Horizontal speed of a freshly served ball, in cells per tick.
+const SERVE_SPEED: f32 = 1.0;
+
+/// Tangents of 0 to 45 whole degrees. A serve's slope is interpolated between them
+/// rather than taken from `f32::tan`, whose last bits can differ between platforms and
+/// would put a replay recorded on one out of sync on another.
+const TAN_DEGREES: [f32; 46] = [
+    0.0, 0.017455066, 0.03492077, 0.05240778, 0.06992681, 0.087488666, 0.10510424, 0.12278456,
+    0.14054084, 0.15838444, 0.17632698, 0.19438031, 0.21255656, 0.23086819, 0.249328, 0.2679492,
+    0.2867454, 0.30573067, 0.3249197, 0.3443276, 0.36397022, 0.38386405, 0.40402624, 0.4244748,
+    0.4452287, 0.46630767, 0.4877326, 0.5095255, 0.53170943, 0.55430907, 0.57735026, 0.6008606,
+    0.62486935, 0.64940757, 0.6745085, 0.70020753, 0.72654253, 0.75355405, 0.78128564, 0.80978405,
+    0.83909965, 0.8692867, 0.90040404, 0.9325151, 0.96568877, 1.0,
+];
+
+/// Who the ball is served toward after a point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ServeDirection {
+    /// Toward the player who just conceded the point.
+    TowardConceder,
+    /// Toward each player in turn, regardless of who scored.
+    Alternate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ServeRules {
+    /// Ticks the ball waits at the centre before every serve.
+    pub delay_ticks: u16,
+    pub direction: ServeDirection,
+    /// Serve at a random angle instead of the classic 45 degrees.
+    pub randomize_angle: bool,
+    /// Steepest random serve, in degrees from the horizontal. Capped at 45.
+    pub max_angle_degrees: f32,
+}
+
+impl Default for ServeRules {
+    fn default() -> Self {
+        Self {
+            delay_ticks: 3 * TICKS_PER_SECOND,
+            direction: ServeDirection::TowardConceder,
+            randomize_angle: false,
+            max_angle_degrees: 35.0,
+        }
+    }
+}
+
+impl ServeRules {
+    /// Side the next serve goes toward, given who conceded and who was served last.
+    pub fn next_receiver(&self, conceder: Side, last_receiver: Side) -> Side {
+        match self.direction {
+            ServeDirection::TowardConceder => conceder,
+            ServeDirection::Alternate => last_receiver.opposite(),
+        }
+    }
+
+    pub fn serve_velocity(&self, toward: Side, rng: &mut SimRng) -> Vec2 {
+        let x_direction = match toward {
+            Side::PlayerOne => -1.0,
+            Side::PlayerTwo => 1.0,
+        };
+        let max_angle = self.max_angle_degrees.clamp(0.0, 45.0);
+        let angle = if self.randomize_angle && max_angle > 0.0 {
+            rng.range_f32(-max_angle, max_angle)
+        } else {
+            45.0
+        };
+
+        // keep the horizontal pace fixed so the angle only changes the vertical drift
+        Vec2::new(x_direction * SERVE_SPEED, slope(angle) * SERVE_SPEED)
+    }
+}
+
+/// Rise over run of a line `degrees` from the horizontal, for angles up to 45 either way.
+fn slope(degrees: f32) -> f32 {
+    let size = degrees.abs().min(45.0);
+    let whole = size.floor();
+    let below = TAN_DEGREES[whole as usize];
+    let above = TAN_DEGREES.get(whole as usize + 1).copied().unwrap_or(below);
+    (below + (above - below) * (size - whole)).copysign(degrees)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ServeState {
+    /// The ball waits at the centre and is served toward `toward` once `ticks_left` runs out.
+    Countdown { ticks_left: u16, toward: Side },
+    InPlay,
+}
+
+impl ServeState {
+    /// Whole seconds left before the serve, rounded up, or `None` while the ball is in play.
+    pub fn seconds_left(&self) -> Option<u16> {
+        match self {
+            ServeState::Countdown { ticks_left, .. } => Some(ticks_left.div_ceil(TICKS_PER_SECOND)),
+            ServeState::InPlay => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slopes_follow_the_tangent() {
+        assert_eq!(slope(45.0), 1.0);
+        assert_eq!(slope(-45.0), -1.0);
+        assert_eq!(slope(0.0), 0.0);
+        assert_eq!(slope(30.0), TAN_DEGREES[30]);
+        for tenth in -450..=450 {
+            let degrees = tenth as f32 / 10.0;
+            assert!((slope(degrees) - degrees.to_radians().tan()).abs() < 1e-3, "{} degrees", degrees);
+        }
+    }
+
+    #[test]
+    fn random_serves_stay_within_the_max_angle() {
+        let rules = ServeRules { randomize_angle: true, max_angle_degrees: 20.0, ..ServeRules::default() };
+        let mut rng = SimRng::new(7);
+        for toward in [Side::PlayerOne, Side::PlayerTwo].into_iter().cycle().take(1000) {
+            let velocity = rules.serve_velocity(toward, &mut rng);
+            assert_eq!(velocity.x, if toward == Side::PlayerOne { -SERVE_SPEED } else { SERVE_SPEED });
+            assert!(velocity.y.abs() <= TAN_DEGREES[20], "{:?} is too steep", velocity);
+        }
+
+        // a fixed serve is the classic 45 degrees whatever the limit
+        let fixed = ServeRules { max_angle_degrees: 20.0, ..ServeRules::default() };
+        assert_eq!(fixed.serve_velocity(Side::PlayerTwo, &mut rng), Vec2::new(1.0, 1.0));
+    }
+}
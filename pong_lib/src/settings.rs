@@ -0,0 +1,139 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::PathBuf;
+use ratatui::style::Color;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use crate::ai::Difficulty;
+use crate::config::ConfigError;
+use crate::error::PongError;
+use crate::history::DEFAULT_API_PORT;
+use crate::input::InputMap;
+use crate::protocol::{PlayerInfo, DEFAULT_PORT};
+use crate::render::Renderer;
+use crate::theme::Theme;
+use crate::TICKS_PER_SECOND;
+
+/// Longest player name, in characters.
+pub const MAX_NAME_LEN: usize = 16;
+
+/// Colours a player can pick for themselves, `None` leaving it to the theme.
+pub const PLAYER_COLORS: [Option<Color>; 13] = [
+    None,
+    Some(Color::Red),
+    Some(Color::Green),
+    Some(Color::Yellow),
+    Some(Color::Blue),
+    Some(Color::Magenta),
+    Some(Color::Cyan),
+    Some(Color::LightRed),
+    Some(Color::LightGreen),
+    Some(Color::LightYellow),
+    Some(Color::LightBlue),
+    Some(Color::LightMagenta),
+    Some(Color::LightCyan),
+];
+
+/// Redraw rates offered in the settings. The screen only changes when the game ticks, so
+/// there is nothing to gain above the tick rate.
+pub const FRAME_RATES: [u16; 3] = [5, 10, TICKS_PER_SECOND];
+
+/// The player's preferences, kept between runs in the user's config directory. Fields
+/// missing from the file, e.g. one written by an older version, take their defaults.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub player_name: String,
+    /// Colour of the player's paddle and name, `None` for the theme's.
+    #[serde(with = "crate::theme::color_text::optional")]
+    pub player_color: Option<Color>,
+    /// Offered when joining a match.
+    pub server_address: String,
+    /// Port the leaderboard is fetched from, the one the server serves its history on.
+    pub api_port: u16,
+    /// How well the computer plays in Play vs CPU.
+    pub difficulty: Difficulty,
+    /// Most times per second the game is redrawn.
+    pub frame_rate: u16,
+    pub renderer: Renderer,
+    /// Name of a built-in theme or of a file in the themes directory.
+    pub theme: String,
+    /// Whether the ball leaves a trail and hits and points are animated.
+    pub effects: bool,
+    pub keys: InputMap,
+    /// Whether the player's paddle follows the mouse.
+    pub mouse_control: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            player_name: String::from("Player"),
+            player_color: None,
+            server_address: format!("127.0.0.1:{}", DEFAULT_PORT),
+            api_port: DEFAULT_API_PORT,
+            difficulty: Difficulty::default(),
+            frame_rate: TICKS_PER_SECOND,
+            renderer: Renderer::default(),
+            theme: Theme::default().name,
+            effects: true,
+            keys: InputMap::default(),
+            mouse_control: false,
+        }
+    }
+}
+
+/// Parses a settings or theme file, blaming `path` for anything wrong with it.
+pub(crate) fn read_json<T: DeserializeOwned>(file: File, path: PathBuf) -> Result<T, PongError> {
+    serde_json::from_reader(BufReader::new(file)).map_err(|error| {
+        if error.is_io() {
+            PongError::Io(error.into())
+        } else {
+            ConfigError::InvalidFile { path, reason: error.to_string() }.into()
+        }
+    })
+}
+
+/// The game's folder in the user's config directory, `None` on platforms without one.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join("rustypong"))
+}
+
+impl Settings {
+    /// Where the settings are stored.
+    pub fn path() -> Option<PathBuf> {
+        config_dir().map(|directory| directory.join("settings.json"))
+    }
+
+    /// Reads the saved settings, or the defaults if none have been saved yet.
+    pub fn load() -> Result<Self, PongError> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(error.into()),
+        };
+        read_json(file, path)
+    }
+
+    /// The chosen theme, the default one if it cannot be loaded.
+    pub fn theme(&self) -> Theme {
+        Theme::named(&self.theme).unwrap_or_default()
+    }
+
+    /// How the player introduces themselves to a server.
+    pub fn player(&self) -> PlayerInfo {
+        PlayerInfo { name: self.player_name.clone(), color: self.player_color }
+    }
+
+    pub fn save(&self) -> Result<(), PongError> {
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory to save settings in"))?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+}
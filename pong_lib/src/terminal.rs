@@ -0,0 +1,96 @@
+use std::io::{self, stdout};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use crossterm::{
+    event::{
+        DisableMouseCapture, EnableMouseCapture, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
+    terminal::{disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand
+};
+
+// whether `report_key_releases` pushed flags that `restore_terminal` has to pop
+static KEY_RELEASES_PUSHED: AtomicBool = AtomicBool::new(false);
+// whether `capture_mouse` turned on mouse reporting
+static MOUSE_CAPTURED: AtomicBool = AtomicBool::new(false);
+
+/// Keeps the terminal in raw mode on the alternate screen while it lives. Dropping it,
+/// whether by returning, by `?` or by a panic unwinding, hands the terminal back in
+/// the state the user left it.
+pub struct TerminalGuard {
+    _private: (),
+}
+
+impl TerminalGuard {
+    pub fn new() -> io::Result<Self> {
+        install_panic_hook();
+        enable_raw_mode()?;
+        stdout().execute(EnterAlternateScreen)?;
+        Ok(Self { _private: () })
+    }
+
+    /// Asks the terminal to report key releases and repeats as well as presses, until the
+    /// guard is dropped. Returns whether it will, terminals without the keyboard
+    /// enhancement protocol only ever report presses.
+    pub fn report_key_releases(&self) -> io::Result<bool> {
+        // Windows reports releases without being asked
+        if cfg!(windows) {
+            return Ok(true);
+        }
+        // a terminal that does not answer the query at all has no support either
+        if !supports_keyboard_enhancement().unwrap_or(false) {
+            return Ok(false);
+        }
+        // releases of keys that type text, such as `w`, only come as escape codes, and
+        // the alternate keys keep shifted characters typed into the chat as they are
+        let flags = KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+            | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+            | KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS
+            | KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES;
+        stdout().execute(PushKeyboardEnhancementFlags(flags))?;
+        KEY_RELEASES_PUSHED.store(true, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    /// Reports mouse movement and clicks as events until the guard is dropped. The
+    /// terminal's own text selection does not work meanwhile.
+    pub fn capture_mouse(&self) -> io::Result<()> {
+        stdout().execute(EnableMouseCapture)?;
+        MOUSE_CAPTURED.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Leaves raw mode and the alternate screen and stops reporting key releases and the
+/// mouse. Safe to call when none of them are active.
+pub fn restore_terminal() {
+    if MOUSE_CAPTURED.swap(false, Ordering::SeqCst) {
+        let _ = stdout().execute(DisableMouseCapture);
+    }
+    if KEY_RELEASES_PUSHED.swap(false, Ordering::SeqCst) {
+        let _ = stdout().execute(PopKeyboardEnhancementFlags);
+    }
+    let _ = disable_raw_mode();
+    let _ = stdout().execute(LeaveAlternateScreen);
+}
+
+// restore the terminal before the panic message is printed, otherwise it is lost on
+// the alternate screen
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            previous_hook(info);
+        }));
+    });
+}
@@ -0,0 +1,192 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::PathBuf;
+use ratatui::style::{Color, Style};
+use serde::{Deserialize, Serialize};
+use crate::error::PongError;
+use crate::settings::{config_dir, read_json};
+use crate::Side;
+
+/// Colours the game and menus are drawn in. Colours are written as names such as
+/// `LightGreen`, as `#RRGGBB` or as a 256 colour palette index; `Reset` is the
+/// terminal's own colour.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Taken from the file name for themes loaded from a file.
+    #[serde(skip)]
+    pub name: String,
+    #[serde(with = "color_text")]
+    pub background: Color,
+    /// Titles, scores and messages.
+    #[serde(with = "color_text")]
+    pub text: Color,
+    #[serde(with = "color_text")]
+    pub border: Color,
+    /// The selected entry in menus.
+    #[serde(with = "color_text")]
+    pub highlight: Color,
+    #[serde(with = "color_text")]
+    pub player_one: Color,
+    #[serde(with = "color_text")]
+    pub player_two: Color,
+    #[serde(with = "color_text")]
+    pub ball: Color,
+    /// A paddle or wall the ball just hit, when effects are on.
+    #[serde(with = "color_text")]
+    pub flash: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: String::from("Default"),
+            background: Color::Reset,
+            text: Color::Reset,
+            border: Color::Reset,
+            highlight: Color::LightGreen,
+            player_one: Color::LightGreen,
+            player_two: Color::LightBlue,
+            ball: Color::Reset,
+            flash: Color::Yellow,
+        }
+    }
+}
+
+impl Theme {
+    /// The themes that come with the game, the default first.
+    pub fn built_in() -> [Theme; 5] {
+        [
+            Theme::default(),
+            Theme {
+                name: String::from("Classic"),
+                highlight: Color::White,
+                player_one: Color::White,
+                player_two: Color::White,
+                ball: Color::White,
+                border: Color::Gray,
+                ..Theme::default()
+            },
+            Theme {
+                name: String::from("Neon"),
+                background: Color::Rgb(16, 0, 32),
+                text: Color::Rgb(255, 240, 255),
+                border: Color::Rgb(255, 0, 200),
+                highlight: Color::Rgb(0, 255, 255),
+                player_one: Color::Rgb(0, 255, 255),
+                player_two: Color::Rgb(255, 0, 200),
+                ball: Color::Rgb(255, 255, 0),
+                flash: Color::Rgb(255, 255, 255),
+            },
+            // Okabe and Ito's palette, told apart with any kind of colour blindness
+            Theme {
+                name: String::from("Colour-blind safe"),
+                highlight: Color::Rgb(240, 228, 66),
+                player_one: Color::Rgb(230, 159, 0),
+                player_two: Color::Rgb(86, 180, 233),
+                ball: Color::Rgb(240, 228, 66),
+                flash: Color::White,
+                ..Theme::default()
+            },
+            Theme {
+                name: String::from("High contrast"),
+                background: Color::Black,
+                text: Color::White,
+                border: Color::White,
+                highlight: Color::Yellow,
+                player_one: Color::Yellow,
+                player_two: Color::Cyan,
+                ball: Color::White,
+                flash: Color::Magenta,
+            },
+        ]
+    }
+
+    /// Where theme files are kept, `<name>.json` for a theme called `name`.
+    pub fn directory() -> Option<PathBuf> {
+        config_dir().map(|directory| directory.join("themes"))
+    }
+
+    /// Names of the built-in themes followed by those in the themes directory.
+    pub fn available() -> Vec<String> {
+        let mut names: Vec<String> = Self::built_in().into_iter().map(|theme| theme.name).collect();
+        let Some(entries) = Self::directory().and_then(|directory| fs::read_dir(directory).ok()) else {
+            return names;
+        };
+        let mut loaded: Vec<String> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+            .filter(|name| !names.contains(name))
+            .collect();
+        loaded.sort();
+        names.append(&mut loaded);
+        names
+    }
+
+    /// The built-in theme called `name`, otherwise the one in its file. Colours the
+    /// file leaves out keep their default.
+    pub fn named(name: &str) -> Result<Theme, PongError> {
+        if let Some(theme) = Self::built_in().into_iter().find(|theme| theme.name == name) {
+            return Ok(theme);
+        }
+        let missing = || io::Error::new(io::ErrorKind::NotFound, format!("no theme called {:?}", name));
+        let path = Self::directory()
+            .map(|directory| directory.join(format!("{}.json", name)))
+            .ok_or_else(missing)?;
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Err(missing().into()),
+            Err(error) => return Err(error.into()),
+        };
+        let mut theme: Theme = read_json(file, path)?;
+        theme.name = String::from(name);
+        Ok(theme)
+    }
+
+    /// Text on the background.
+    pub fn base(&self) -> Style {
+        Style::new().fg(self.text).bg(self.background)
+    }
+
+    pub fn player(&self, side: Side) -> Color {
+        match side {
+            Side::PlayerOne => self.player_one,
+            Side::PlayerTwo => self.player_two,
+        }
+    }
+}
+
+// colours as the text ratatui parses and prints, rather than its enum
+pub(crate) mod color_text {
+    use ratatui::style::Color;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(color)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(|_| D::Error::custom(format!("unknown colour {:?}", text)))
+    }
+
+    // the same for a colour that may be left out, written as `null`
+    pub mod optional {
+        use ratatui::style::Color;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(color: &Option<Color>, serializer: S) -> Result<S::Ok, S::Error> {
+            match color {
+                Some(color) => super::serialize(color, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Color>, D::Error> {
+            #[derive(Deserialize)]
+            struct Text(#[serde(with = "super")] Color);
+            Ok(Option::<Text>::deserialize(deserializer)?.map(|Text(color)| color))
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use ratatui::layout::Rect;
+use crate::centered_rect;
+use crate::collision::Vec2;
+
+/// Smallest fraction of its logical size the arena may be shrunk to before the terminal
+/// counts as too small to play in.
+pub const MIN_SCALE: f32 = 0.5;
+
+/// Rows of the status bar above the arena.
+pub const HUD_ROWS: u16 = 2;
+
+/// Maps the logical arena onto the part of the terminal it is drawn in. The border
+/// always takes one cell, the interior is scaled by the same factor on both axes and the
+/// arena is centred in whatever space the status bar leaves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ArenaView {
+    arena: Rect,
+    /// Where the arena, border included, lands on screen.
+    pub screen: Rect,
+    /// The status bar, just above the arena and as wide as it.
+    pub hud: Rect,
+    scale_x: f32,
+    scale_y: f32,
+}
+
+// cells inside the one cell border
+fn interior(size: u16) -> u16 {
+    size.saturating_sub(2).max(1)
+}
+
+impl ArenaView {
+    /// Fits `arena` into `available`, `None` when it would have to shrink past `MIN_SCALE`.
+    pub fn fit(arena: Rect, available: Rect) -> Option<Self> {
+        let (min_width, min_height) = Self::min_size(arena);
+        if available.width < min_width || available.height < min_height {
+            return None;
+        }
+        let available = Rect::new(available.x, available.y + HUD_ROWS, available.width, available.height - HUD_ROWS);
+
+        let scale = (interior(available.width) as f32 / interior(arena.width) as f32)
+            .min(interior(available.height) as f32 / interior(arena.height) as f32);
+        let scaled = |size: u16| ((interior(size) as f32 * scale).round() as u16).max(1);
+        let width = (scaled(arena.width) + 2).min(available.width);
+        let height = (scaled(arena.height) + 2).min(available.height);
+
+        let screen = centered_rect(available, width, height);
+        Some(Self {
+            arena,
+            screen,
+            hud: Rect::new(screen.x, screen.y - HUD_ROWS, screen.width, HUD_ROWS),
+            scale_x: interior(width) as f32 / interior(arena.width) as f32,
+            scale_y: interior(height) as f32 / interior(arena.height) as f32,
+        })
+    }
+
+    /// Screen cells inside the border.
+    pub fn interior(&self) -> Rect {
+        let Rect { x, y, width, height } = self.screen;
+        Rect::new(x + 1, y + 1, interior(width), interior(height))
+    }
+
+    /// Smallest terminal `fit` accepts for `arena`, status bar included.
+    pub fn min_size(arena: Rect) -> (u16, u16) {
+        let shrunk = |size: u16| (interior(size) as f32 * MIN_SCALE).ceil() as u16 + 2;
+        (shrunk(arena.width), shrunk(arena.height) + HUD_ROWS)
+    }
+
+    /// Screen cells covered by something of logical `size` at logical `position`. It is
+    /// kept inside the border and never shrinks below a single cell.
+    pub fn map(&self, position: Vec2, size: (u16, u16)) -> Rect {
+        let inner_width = interior(self.screen.width);
+        let inner_height = interior(self.screen.height);
+        let offset = |logical: f32, origin: u16, scale: f32, limit: u16| {
+            ((logical - (origin + 1) as f32) * scale).round().clamp(0.0, (limit - 1) as f32) as u16
+        };
+        let x = offset(position.x, self.arena.x, self.scale_x, inner_width);
+        let y = offset(position.y, self.arena.y, self.scale_y, inner_height);
+        let width = ((size.0 as f32 * self.scale_x).round() as u16).clamp(1, inner_width - x);
+        let height = ((size.1 as f32 * self.scale_y).round() as u16).clamp(1, inner_height - y);
+        Rect::new(self.screen.x + 1 + x, self.screen.y + 1 + y, width, height)
+    }
+
+    /// Logical height at the middle of screen `row`, rows outside the arena count as its
+    /// nearest edge.
+    pub fn logical_y(&self, row: u16) -> f32 {
+        let offset = row.saturating_sub(self.screen.y + 1).min(interior(self.screen.height) - 1);
+        (offset as f32 + 0.5) / self.scale_y + (self.arena.y + 1) as f32
+    }
+}
@@ -0,0 +1,106 @@
+use std::net::SocketAddr;
+use std::thread;
+use pong_lib::error::PongError;
+use pong_lib::history::MatchHistory;
+use serde_json::json;
+use warp::http::StatusCode;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+/// Serves the match history over HTTP from a thread of its own, for as long as the
+/// server runs:
+///
+/// - `GET /matches`, every match finished, oldest first
+/// - `GET /players`, every player's totals, highest rated first
+/// - `GET /players/<name>`, one player's totals, 404 if they have not played
+pub fn spawn(history: MatchHistory, address: SocketAddr) {
+    let with_history = warp::any().map(move || history.clone());
+    let matches = warp::path!("matches")
+        .and(with_history.clone())
+        .map(|history: MatchHistory| reply(history.load()));
+    let players = warp::path!("players")
+        .and(with_history.clone())
+        .map(|history: MatchHistory| reply(history.player_stats()));
+    let player = warp::path!("players" / String)
+        .and(with_history)
+        .map(|name: String, history: MatchHistory| match history.stats_for(&percent_decode(&name)) {
+            Ok(stats) if stats.matches == 0 => {
+                warp::reply::with_status(warp::reply::json(&json!({ "error": "no matches played" })), StatusCode::NOT_FOUND)
+                    .into_response()
+            },
+            stats => reply(stats),
+        });
+    let routes = warp::get().and(matches.or(players).unify().or(player).unify());
+
+    thread::spawn(move || {
+        let runtime = match tokio::runtime::Runtime::new() {
+            Ok(runtime) => runtime,
+            Err(error) => {
+                println!("HTTP API not started: {}", error);
+                return;
+            },
+        };
+        runtime.block_on(warp::serve(routes).run(address));
+    });
+}
+
+// `result` as JSON, or the error with a 500 status
+fn reply<T: serde::Serialize>(result: Result<T, PongError>) -> Response {
+    match result {
+        Ok(body) => warp::reply::json(&body).into_response(),
+        Err(error) => {
+            let body = warp::reply::json(&json!({ "error": error.to_string() }));
+            warp::reply::with_status(body, StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        },
+    }
+}
+
+// a path segment with its `%XX` escapes decoded, e.g. `Ann%20Lee` to `Ann Lee`
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes.get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_are_decoded() {
+        assert_eq!(percent_decode("Ann%20Lee"), "Ann Lee");
+        assert_eq!(percent_decode("%41%62c"), "Abc");
+        assert_eq!(percent_decode("Zo%C3%AB"), "Zoë");
+    }
+
+    #[test]
+    fn a_plus_is_left_alone() {
+        // only query strings use `+` for a space, not paths
+        assert_eq!(percent_decode("Ann+Lee"), "Ann+Lee");
+    }
+
+    #[test]
+    fn malformed_escapes_are_kept_as_they_are() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%2"), "%2");
+        assert_eq!(percent_decode("%zz%20"), "%zz ");
+        assert_eq!(percent_decode("%%41"), "%A");
+        assert_eq!(percent_decode("%FF"), "\u{FFFD}");
+    }
+}
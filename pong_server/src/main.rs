@@ -1,10 +1,18 @@
-use std::{net::{TcpListener, TcpStream, IpAddr}, str::FromStr, os::unix::fs::PermissionsExt, time::Duration};
-use std::io::{prelude::*, BufReader, Write};
-use std::io;
-use pong_lib::{TerminalOutput};
+mod api;
+mod ranked;
 
+use std::{net::{SocketAddr, TcpListener, TcpStream, IpAddr}, str::FromStr};
+use pong_lib::{Side, TerminalOutput};
+use pong_lib::config::ArenaConfig;
+use pong_lib::error::PongError;
+use pong_lib::history::{MatchHistory, DEFAULT_API_PORT, DEFAULT_HISTORY_PATH};
+use pong_lib::protocol::{welcome_client, PlayerInfo, DEFAULT_PORT};
+use pong_lib::rng::seed_from_time;
+use pong_lib::rules::MatchRules;
+use pong_lib::serve::ServeRules;
 
-fn main() -> io::Result<()>{
+
+fn main() -> Result<(), PongError> {
     let args: Vec<String> = std::env::args().collect();
     
     if args.len() < 2 {
@@ -15,25 +23,90 @@ fn main() -> io::Result<()>{
         .expect("Error parsing Ip Address")
         .to_string();
     println!("IP Address: {:?}", ip_address);
-    let ip_address_and_port = format!("{}:3737", ip_address);
+    let ip_address_and_port = format!("{}:{}", ip_address, DEFAULT_PORT);
+
+    // set up the game first so bad rules are reported before anyone connects
+    let seed = flag_value(&args, "--seed").unwrap_or_else(seed_from_time);
+    println!("Match seed: {}", seed);
+    let default_arena = ArenaConfig::default();
+    let arena = ArenaConfig {
+        width: flag_value(&args, "--width").unwrap_or(default_arena.width),
+        height: flag_value(&args, "--height").unwrap_or(default_arena.height),
+        paddle_width: flag_value(&args, "--paddle-width").unwrap_or(default_arena.paddle_width),
+        paddle_height: flag_value(&args, "--paddle-height").unwrap_or(default_arena.paddle_height),
+        paddle_speed: flag_value(&args, "--paddle-speed").unwrap_or(default_arena.paddle_speed),
+    };
+    let random_serve = args.iter().any(|arg| arg == "--random-serve");
+    let default_rules = MatchRules::default();
+    let match_rules = MatchRules {
+        points_per_game: flag_value(&args, "--points").unwrap_or(default_rules.points_per_game),
+        win_by: flag_value(&args, "--win-by").unwrap_or(default_rules.win_by),
+        games: flag_value(&args, "--best-of").unwrap_or(default_rules.games),
+        time_limit_secs: flag_value(&args, "--time-limit"),
+    };
+    // every match is kept, and served over HTTP along with each player's totals
+    let history = MatchHistory::new(flag_value(&args, "--history").unwrap_or_else(|| String::from(DEFAULT_HISTORY_PATH)));
+    let new_match = |seed| -> Result<TerminalOutput, PongError> {
+        let mut term = TerminalOutput::new(arena, seed)?;
+        term.set_logging(true);
+        if random_serve {
+            term.set_serve_rules(ServeRules { randomize_angle: true, ..ServeRules::default() });
+        }
+        term.set_match_rules(match_rules)?;
+        term.set_history(history.clone());
+        Ok(term)
+    };
+    let mut term = new_match(seed)?;
+    let (min_width, min_height) = arena.min_terminal_size();
+    println!("Arena {}x{}, players need a terminal of at least {}x{}", arena.width, arena.height, min_width, min_height);
+
+    println!("Match history: {}", history.path().display());
+    let api_address = SocketAddr::new(ip_address.parse().unwrap(), flag_value(&args, "--http-port").unwrap_or(DEFAULT_API_PORT));
+    api::spawn(history.clone(), api_address);
+    println!("HTTP API on http://{}", api_address);
 
     let tcp_listener = TcpListener::bind(ip_address_and_port).unwrap();
 
+    // players are paired by rating rather than in the order they connect, and nobody
+    // can watch
+    if args.iter().any(|arg| arg == "--ranked") {
+        return ranked::run(tcp_listener, arena, &history, seed, new_match);
+    }
+
     // connect players
-    let mut player_one = tcp_listener.accept().unwrap().0;
-    println!("player 1 tcp stream: {:?}", player_one);
-    let mut player_two = tcp_listener.accept().unwrap().0;
-    println!("player 2 tcp stream: {:?}", player_two);
+    let (player_one, player_one_info) = accept_player(&tcp_listener, arena, Side::PlayerOne)?;
+    println!("player 1 ({}) tcp stream: {:?}", player_one_info.name, player_one);
+    let (player_two, player_two_info) = accept_player(&tcp_listener, arena, Side::PlayerTwo)?;
+    println!("player 2 ({}) tcp stream: {:?}", player_two_info.name, player_two);
+    term.set_players([player_one_info, player_two_info]);
 
     // set streams as non blocking
     //player_one.set_nonblocking(true).expect("set_nonblocking call failed");
     //player_two.set_nonblocking(true).expect("set_nonblocking call failed");
 
     // begin game logic and loop sending of data to client
-	let mut user_buffer = String::new();
-    let mut term = TerminalOutput::new(80,40);
-
     println!("Beginning game logic...");
-    term.run_server(&mut player_one, &mut player_two)?;
+    term.start_recording();
+    // saves a replay of each match as it ends
+    term.run_server(player_one, player_two, Some(&tcp_listener))?;
     Ok(())
 }
+
+// waits for a client that completes the handshake, turning away any that do not, and
+// returns it along with who they say they are
+fn accept_player(listener: &TcpListener, arena: ArenaConfig, side: Side) -> Result<(TcpStream, PlayerInfo), PongError> {
+    loop {
+        let (mut stream, address) = listener.accept()?;
+        match welcome_client(&mut stream, arena, Some(side)) {
+            Ok(player) => return Ok((stream, player)),
+            Err(error) => println!("Turned away {}: {}", address, error),
+        }
+    }
+}
+
+// value following `flag` on the command line, e.g. `--points 21`
+fn flag_value<T: FromStr>(args: &[String], flag: &str) -> Option<T> {
+    let index = args.iter().position(|arg| arg == flag)?;
+    let value = args.get(index + 1).expect("Missing value after flag");
+    Some(value.parse().unwrap_or_else(|_| panic!("Error parsing value of {}", flag)))
+}
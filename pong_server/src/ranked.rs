@@ -0,0 +1,148 @@
+use std::collections::HashSet;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use pong_lib::{Side, TerminalOutput};
+use pong_lib::config::ArenaConfig;
+use pong_lib::error::PongError;
+use pong_lib::history::MatchHistory;
+use pong_lib::matchmaking::RankedQueue;
+use pong_lib::protocol::{greet_client, send_message, PlayerInfo, Rejection, ServerMessage, PROTOCOL_VERSION};
+
+/// How often the queue takes in new players and looks for pairs.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// someone waiting for a ranked match
+struct Queued {
+    stream: TcpStream,
+    address: SocketAddr,
+    player: PlayerInfo,
+}
+
+/// Runs a ranked server on `listener`: everyone who connects is queued at their rating
+/// from `history`, and each pair the queue finds plays on a thread of its own, in a game
+/// `new_match` sets up from a seed. Match seeds count up from `seed`.
+pub fn run(
+    listener: TcpListener,
+    arena: ArenaConfig,
+    history: &MatchHistory,
+    seed: u64,
+    mut new_match: impl FnMut(u64) -> Result<TerminalOutput, PongError>,
+) -> Result<(), PongError> {
+    let (greeted_sender, greeted) = mpsc::channel();
+    thread::spawn(move || accept_players(listener, arena, greeted_sender));
+    let mut queue = RankedQueue::default();
+    // names of everyone in a match, nobody else can queue under them until it ends
+    let playing: Arc<Mutex<HashSet<String>>> = Arc::default();
+    let mut next_seed = seed;
+    println!("Ranked queue open, waiting for players...");
+    loop {
+        for newcomer in greeted.try_iter() {
+            let newcomer = newcomer?;
+            let address = newcomer.address;
+            if let Err(error) = queue_player(newcomer, arena, history, &mut queue, &playing) {
+                println!("Turned away {}: {}", address, error);
+            }
+        }
+        queue.retain(|waiting| {
+            let connected = still_connected(&waiting.stream);
+            if !connected {
+                println!("{} left the queue", waiting.player.name);
+            }
+            connected
+        });
+
+        while let Some((player_one, player_two)) = queue.pair(Instant::now()) {
+            println!("Match seed: {}", next_seed);
+            let term = new_match(next_seed)?;
+            next_seed = next_seed.wrapping_add(1);
+            start_match(term, arena, [player_one, player_two], Arc::clone(&playing));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// accepts connections and greets each on a thread of its own, so one that never says
+// hello cannot hold up the queue, handing everyone greeted to `greeted`. A failure to
+// accept is handed over too, and ends it.
+fn accept_players(listener: TcpListener, arena: ArenaConfig, greeted: mpsc::Sender<io::Result<Queued>>) {
+    loop {
+        let (mut stream, address) = match listener.accept() {
+            Ok(connection) => connection,
+            Err(error) => {
+                let _ = greeted.send(Err(error));
+                return;
+            },
+        };
+        let greeted = greeted.clone();
+        thread::spawn(move || match greet_client(&mut stream, arena) {
+            Ok(player) => {
+                let _ = greeted.send(Ok(Queued { stream, address, player }));
+            },
+            Err(error) => println!("Turned away {}: {}", address, error),
+        });
+    }
+}
+
+// queues a greeted client at their rating, unless their name is taken
+fn queue_player(
+    newcomer: Queued,
+    arena: ArenaConfig,
+    history: &MatchHistory,
+    queue: &mut RankedQueue<Queued>,
+    playing: &Mutex<HashSet<String>>,
+) -> Result<(), PongError> {
+    let Queued { mut stream, address, player } = newcomer;
+    // ratings go by name, so two players sharing one would muddle them
+    let taken = queue.contains(|waiting| waiting.player.name == player.name)
+        || playing.lock().unwrap().contains(&player.name);
+    if taken {
+        send_message(&mut stream, &ServerMessage::Rejected(Rejection::NameInUse))?;
+        return Err(PongError::NameInUse { name: player.name });
+    }
+    let rating = history.stats_for(&player.name)?.rating;
+    send_message(&mut stream, &ServerMessage::Queued { version: PROTOCOL_VERSION, arena, rating })?;
+    println!("{} queued at {} from {}, {} waiting", player.name, rating, address, queue.len() + 1);
+    queue.join(Queued { stream, address, player }, rating, Instant::now());
+    Ok(())
+}
+
+// a queued client sends nothing until it is welcomed, so anything to read, even the end
+// of the connection, means it has given up waiting
+fn still_connected(stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let waiting = matches!(stream.peek(&mut [0]), Err(error) if error.kind() == io::ErrorKind::WouldBlock);
+    stream.set_nonblocking(false).is_ok() && waiting
+}
+
+// welcomes the pair to their match and plays it, and any rematches, on a thread of its own
+fn start_match(mut term: TerminalOutput, arena: ArenaConfig, players: [Queued; 2], playing: Arc<Mutex<HashSet<String>>>) {
+    let names = [players[0].player.name.clone(), players[1].player.name.clone()];
+    println!("Starting {} ({}) v {} ({})", names[0], players[0].address, names[1], players[1].address);
+    playing.lock().unwrap().extend(names.clone());
+
+    thread::spawn(move || {
+        let [mut player_one, mut player_two] = players;
+        for (side, queued) in [(Side::PlayerOne, &mut player_one), (Side::PlayerTwo, &mut player_two)] {
+            // one who has gone since forfeits as soon as the match reads from them
+            let welcome = ServerMessage::Welcome { version: PROTOCOL_VERSION, arena, side: Some(side) };
+            if let Err(error) = send_message(&mut queued.stream, &welcome) {
+                println!("{}: {}", queued.player.name, error);
+            }
+        }
+        term.set_players([player_one.player, player_two.player]);
+        term.start_recording();
+        if let Err(error) = term.run_server(player_one.stream, player_two.stream, None) {
+            println!("{} v {}: {}", names[0], names[1], error);
+        }
+        println!("{} v {} is over", names[0], names[1]);
+        let mut playing = playing.lock().unwrap();
+        for name in &names {
+            playing.remove(name);
+        }
+    });
+}